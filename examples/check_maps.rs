@@ -0,0 +1,17 @@
+//! Prints what `pocket_daemons::asset_check::maps` finds wrong with the maps,
+//! and fails if any of it breaks the game.
+
+use std::path::Path;
+use std::process::ExitCode;
+
+use pocket_daemons::asset_check::maps::check_maps;
+
+fn main() -> ExitCode {
+    let report = check_maps(&Path::new(env!("CARGO_MANIFEST_DIR")).join("assets"));
+    print!("{}", report);
+    if report.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
 use bevy::window::WindowMode;
 use pocket_daemons::GamePlugin; // ToDo: Replace bevy_game with your new crate name.
+use pocket_daemons::TouchControlsPlugin;
 
 #[bevy_main]
 fn main() {
@@ -15,6 +16,7 @@ fn main() {
                 ..default()
             }),
             GamePlugin,
+            TouchControlsPlugin,
         ))
         .run();
 }
@@ -0,0 +1,43 @@
+//! Checks run over the asset files outside the game, so broken references
+//! are caught before they show up at runtime.
+
+use std::fmt;
+
+pub mod locales;
+pub mod maps;
+mod tmx;
+
+/// Problems found by a check, as messages ready to print.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// Things that break the game, like references to nothing.
+    pub errors: Vec<String>,
+    /// Things the game copes with, like text that falls back to English.
+    pub warnings: Vec<String>,
+}
+
+impl Report {
+    pub fn error(&mut self, message: impl Into<String>) {
+        self.errors.push(message.into());
+    }
+
+    pub fn warning(&mut self, message: impl Into<String>) {
+        self.warnings.push(message.into());
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for error in &self.errors {
+            writeln!(f, "error: {}", error)?;
+        }
+        for warning in &self.warnings {
+            writeln!(f, "warning: {}", warning)?;
+        }
+        writeln!(f, "{} errors, {} warnings", self.errors.len(), self.warnings.len())
+    }
+}
@@ -0,0 +1,174 @@
+//! Compares every locale with [`DEFAULT_LOCALE`], which the others fall back
+//! to, and checks that the dialogs maps refer to exist.
+//!
+//! Run with `cargo run --example check_locales`.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use super::tmx::loaded_maps;
+use super::tmx::maps_in;
+use super::tmx::read_objects;
+use super::Report;
+use crate::map::DialogReference;
+use crate::text_loading::load_locale;
+use crate::text_loading::Dialog;
+use crate::text_loading::GameText;
+use crate::text_loading::DEFAULT_LOCALE;
+use crate::text_variables::placeholder_keys;
+
+/// Checks the locales in `assets/locales` and the maps in `assets/maps/areas`.
+pub fn check_locales(assets: &Path) -> Report {
+    let mut report = Report::default();
+    let locales = assets.join("locales");
+    let reference = match load_locale(&locales, DEFAULT_LOCALE) {
+        Ok(reference) => reference,
+        Err(error) => {
+            report.error(format!("{}: {}", DEFAULT_LOCALE, error));
+            return report;
+        }
+    };
+
+    match locale_names(&locales) {
+        Ok(names) => {
+            for locale in names.iter().filter(|locale| *locale != DEFAULT_LOCALE) {
+                match load_locale(&locales, locale) {
+                    Ok(text) => compare_locale(&reference, locale, &text, &mut report),
+                    Err(error) => report.error(format!("{}: {}", locale, error)),
+                }
+            }
+        }
+        Err(error) => report.error(format!("Could not list locales: {}", error)),
+    }
+
+    check_dialog_references(&reference, &assets.join("maps/areas"), &mut report);
+    report
+}
+
+/// Locales in `locales`, one per `.ron` file beside the folders of text.
+fn locale_names(locales: &Path) -> std::io::Result<BTreeSet<String>> {
+    Ok(std::fs::read_dir(locales)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|extension| extension == "ron"))
+        .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+        .collect())
+}
+
+/// Keys of the placeholders in every span and choice of `dialog`.
+fn dialog_placeholders(dialog: &Dialog) -> BTreeSet<&str> {
+    dialog.iter()
+        .flat_map(|page| page.spans.iter().map(|span| span.text.as_str())
+            .chain(page.choices.iter().map(|choice| choice.text.as_str())))
+        .flat_map(placeholder_keys)
+        .collect()
+}
+
+/// Missing keys fall back and are only warned about. Keys the reference
+/// doesn't have, and placeholders that differ from the reference's, are
+/// errors.
+fn compare_locale(reference: &GameText, locale: &str, text: &GameText, report: &mut Report) {
+    let compare = |report: &mut Report, kind: &str, reference_keys: BTreeSet<&String>, keys: BTreeSet<&String>| {
+        for key in reference_keys.difference(&keys) {
+            report.warning(format!("{}: {} `{}` is not translated", locale, kind, key));
+        }
+        for key in keys.difference(&reference_keys) {
+            report.error(format!("{}: {} `{}` is not in {}", locale, kind, key, DEFAULT_LOCALE));
+        }
+    };
+    compare(report, "string", reference.strings.keys().collect(), text.strings.keys().collect());
+    compare(report, "dialog", reference.dialogs.keys().collect(), text.dialogs.keys().collect());
+
+    let mismatch = |report: &mut Report, kind: &str, key: &str, expected: BTreeSet<&str>, found: BTreeSet<&str>| {
+        if expected != found {
+            report.error(format!(
+                "{}: {} `{}` has placeholders {:?}, {} has {:?}",
+                locale, kind, key, found, DEFAULT_LOCALE, expected,
+            ));
+        }
+    };
+    let mut strings: Vec<_> = text.strings.iter().collect();
+    strings.sort();
+    for (key, string) in strings {
+        if let Some(expected) = reference.strings.get(key) {
+            mismatch(report, "string", key, placeholder_keys(expected).collect(), placeholder_keys(string).collect());
+        }
+    }
+    let mut dialogs: Vec<_> = text.dialogs.iter().collect();
+    dialogs.sort_by_key(|(key, _)| *key);
+    for (key, dialog) in dialogs {
+        if let Some(expected) = reference.dialog(key) {
+            mismatch(report, "dialog", key, dialog_placeholders(expected), dialog_placeholders(dialog));
+        }
+    }
+}
+
+/// Every [`DialogReference`] on the maps in `areas` has to name a dialog.
+fn check_dialog_references(reference: &GameText, areas: &Path, report: &mut Report) {
+    let maps = match maps_in(areas) {
+        Ok(maps) => maps,
+        Err(error) => {
+            report.error(format!("Could not list maps: {}", error));
+            return;
+        }
+    };
+    for (name, path) in loaded_maps(maps) {
+        let objects = match read_objects(&path) {
+            Ok(objects) => objects,
+            Err(error) => {
+                report.error(format!("{}: {}", name, error));
+                continue;
+            }
+        };
+        for object in objects {
+            let Some(dialog) = object.field::<DialogReference>("reference") else { continue };
+            if reference.dialog(dialog).is_none() {
+                report.error(format!("{}: object {} refers to unknown dialog `{}`", name, object.id, dialog));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_locales_check_out() {
+        let report = check_locales(&Path::new(env!("CARGO_MANIFEST_DIR")).join("assets"));
+        assert!(report.is_ok(), "{}", report);
+    }
+
+    fn game_text(table: &str) -> GameText {
+        let mut text = GameText::default();
+        text.merge("test.ron", ron::from_str(table).unwrap());
+        text
+    }
+
+    #[test]
+    fn reports_keys_and_placeholders() {
+        let reference = game_text(r#"TextTable(
+            strings: { "greeting": "Hello {player_name}!", "farewell": "Bye." },
+            dialogs: {
+                "game_saved": Dialog([Page(speaker: "sign", mood: "neutral", spans: [Span(text: "Saved.")])]),
+                "hiker_challenge": Dialog([Page(speaker: "hiker", mood: "neutral", spans: [Span(text: "Fight!")])]),
+            },
+        )"#);
+        let text = game_text(r#"TextTable(
+            strings: { "greeting": "Bonjour {player_name} !", "no_such_string": "?" },
+            dialogs: {
+                "game_saved": Dialog([Page(speaker: "sign", mood: "neutral", spans: [Span(text: "{player_name} a sauvegardé.")])]),
+            },
+        )"#);
+
+        let mut report = Report::default();
+        compare_locale(&reference, "fr", &text, &mut report);
+        assert_eq!(report.errors, [
+            "fr: string `no_such_string` is not in en-US",
+            "fr: dialog `game_saved` has placeholders {\"player_name\"}, en-US has {}",
+        ]);
+        assert_eq!(report.warnings, [
+            "fr: string `farewell` is not translated",
+            "fr: dialog `hiker_challenge` is not translated",
+        ]);
+    }
+}
@@ -0,0 +1,163 @@
+//! Checks the Tiled maps under `assets/maps/areas` for the references that
+//! would otherwise only fail once the player gets to them: exits, spawn
+//! directions and sprites.
+//!
+//! Run with `cargo run --example check_maps`.
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy::reflect::TypeInfo;
+use bevy::reflect::Typed;
+use bevy_ecs_tiled::prelude::*;
+
+use super::tmx::loaded_maps;
+use super::tmx::maps_in;
+use super::tmx::read_objects;
+use super::tmx::MapObject;
+use super::tmx::EMBEDDED_SUFFIX;
+use super::Report;
+use crate::loading::MapAssets;
+use crate::loading::TextureAssets;
+use crate::map::spawn_direction;
+use crate::map::ExitData;
+use crate::map::InitSprite;
+use crate::map::SpawnData;
+
+/// The objects of one `.tmx` file.
+struct AreaFile {
+    file: String,
+    /// What [`ExitData::map`] calls the map.
+    map: String,
+    objects: Vec<MapObject>,
+}
+
+/// Checks every map in `assets/maps/areas`.
+pub fn check_maps(assets: &Path) -> Report {
+    let mut report = Report::default();
+    let maps = match maps_in(&assets.join("maps/areas")) {
+        Ok(maps) => maps,
+        Err(error) => {
+            report.error(format!("Could not list maps: {}", error));
+            return report;
+        }
+    };
+    let mut files = Vec::new();
+    for (name, path) in loaded_maps(maps) {
+        let file = format!("{}.tmx", name);
+        match read_objects(&path) {
+            Ok(objects) => files.push(AreaFile {
+                map: name.strip_suffix(EMBEDDED_SUFFIX).unwrap_or(&name).to_string(),
+                file,
+                objects,
+            }),
+            Err(error) => report.error(format!("{}: {}", file, error)),
+        }
+    }
+    check_files(&files, &mut report);
+    report
+}
+
+/// Whether `C` has a field `name` of type `T`, so `get_field` finds it.
+fn has_field<C: Typed, T: 'static>(name: &str) -> bool {
+    match C::type_info() {
+        TypeInfo::Struct(info) => info.field(name).is_some_and(|field| field.is::<T>()),
+        _ => false,
+    }
+}
+
+fn spawn_names(file: &AreaFile) -> BTreeSet<&str> {
+    file.objects.iter()
+        .filter_map(|object| object.field::<SpawnData>("name"))
+        .collect()
+}
+
+fn check_files(files: &[AreaFile], report: &mut Report) {
+    for file in files {
+        if !has_field::<MapAssets, Handle<TiledMap>>(&file.map) {
+            report.warning(format!("{}: map `{}` is not in MapAssets, so nothing can lead to it", file.file, file.map));
+        }
+
+        let mut spawns = BTreeSet::new();
+        for object in &file.objects {
+            let at = format!("{} object {}", file.file, object.id);
+
+            if let Some(name) = object.field::<SpawnData>("name") {
+                if !spawns.insert(name) {
+                    report.error(format!("{}: spawn `{}` is already on this map", at, name));
+                }
+                let direction = object.field::<SpawnData>("from_direction").unwrap_or_default();
+                if spawn_direction(direction).is_none() {
+                    report.error(format!("{}: spawn `{}` has invalid direction `{}`", at, name, direction));
+                }
+            }
+
+            if let (Some(map), Some(spawn)) = (object.field::<ExitData>("map"), object.field::<ExitData>("spawn")) {
+                let targets: Vec<&AreaFile> = files.iter().filter(|target| target.map == map).collect();
+                if !has_field::<MapAssets, Handle<TiledMap>>(map) || targets.is_empty() {
+                    report.error(format!("{}: exit leads to unknown map `{}`", at, map));
+                }
+                for target in targets.into_iter().filter(|target| !spawn_names(target).contains(spawn)) {
+                    report.error(format!("{}: exit leads to spawn `{}`, which {} doesn't have", at, spawn, target.file));
+                }
+            }
+
+            if let Some(fields) = object.component::<InitSprite>() {
+                let field = |name: &str| fields.get(name).map_or("", String::as_str);
+                if !has_field::<TextureAssets, Handle<Image>>(field("reference")) {
+                    report.error(format!("{}: sprite has unknown texture `{}`", at, field("reference")));
+                }
+                if !has_field::<TextureAssets, Handle<TextureAtlasLayout>>(field("atlas_reference")) {
+                    report.error(format!("{}: sprite has unknown atlas `{}`", at, field("atlas_reference")));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset_check::tmx::parse_objects;
+
+    #[test]
+    fn shipped_maps_check_out() {
+        let report = check_maps(&Path::new(env!("CARGO_MANIFEST_DIR")).join("assets"));
+        assert!(report.is_ok(), "{}", report);
+    }
+
+    #[test]
+    fn reports_broken_objects() {
+        let objects = parse_objects(r#"<map><objectgroup id="3" name="objects">
+            <object id="1"><properties>
+                <property name="spawn_data" type="class" propertytype="pocket_daemons::map::SpawnData">
+                    <properties><property name="name" value="north"/><property name="from_direction" value="up"/></properties>
+                </property>
+            </properties></object>
+            <object id="2"><properties>
+                <property name="exit_data" type="class" propertytype="pocket_daemons::map::ExitData">
+                    <properties><property name="map" value="clearing"/><property name="spawn" value="nowhere"/></properties>
+                </property>
+            </properties></object>
+            <object id="3"><properties>
+                <property name="exit_data" type="class" propertytype="pocket_daemons::map::ExitData">
+                    <properties><property name="map" value="cave"/><property name="spawn" value="north"/></properties>
+                </property>
+                <property name="init_sprite" type="class" propertytype="pocket_daemons::map::InitSprite">
+                    <properties><property name="reference" value="mob"/></properties>
+                </property>
+            </properties></object>
+        </objectgroup></map>"#).unwrap();
+        let files = [AreaFile { file: "clearing.tmx".to_string(), map: "clearing".to_string(), objects }];
+
+        let mut report = Report::default();
+        check_files(&files, &mut report);
+        assert_eq!(report.errors, [
+            "clearing.tmx object 1: spawn `north` has invalid direction `up`",
+            "clearing.tmx object 2: exit leads to spawn `nowhere`, which clearing.tmx doesn't have",
+            "clearing.tmx object 3: exit leads to unknown map `cave`",
+            "clearing.tmx object 3: sprite has unknown atlas ``",
+        ]);
+    }
+}
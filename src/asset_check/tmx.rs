@@ -0,0 +1,117 @@
+//! Just enough of the Tiled map format to read the components placed on map
+//! objects, without loading the map into the game.
+
+use std::any::type_name;
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::path::Path;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum TmxError {
+    #[error("Could not read map: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse map: {0}")]
+    Xml(#[from] roxmltree::Error),
+}
+
+/// An object on one of the map's object layers.
+#[derive(Debug, Default)]
+pub struct MapObject {
+    pub id: u32,
+    /// Fields of each component on the object, by type path. Tiled leaves
+    /// out fields that have their default value.
+    pub components: HashMap<String, HashMap<String, String>>,
+}
+
+impl MapObject {
+    /// The fields of the `T` component, if the object has one.
+    pub fn component<T>(&self) -> Option<&HashMap<String, String>> {
+        self.components.get(type_name::<T>())
+    }
+
+    /// A field of the `T` component, empty if Tiled left it out.
+    pub fn field<T>(&self, field: &str) -> Option<&str> {
+        self.component::<T>()
+            .map(|fields| fields.get(field).map_or("", String::as_str))
+    }
+}
+
+/// The objects of the map at `path`. Objects inside tilesets, like collision
+/// shapes, aren't placed on the map and are left out.
+pub fn read_objects(path: &Path) -> Result<Vec<MapObject>, TmxError> {
+    Ok(parse_objects(&std::fs::read_to_string(path)?)?)
+}
+
+/// The objects of a map read into `text`, see [`read_objects`].
+pub fn parse_objects(text: &str) -> Result<Vec<MapObject>, roxmltree::Error> {
+    let document = roxmltree::Document::parse(text)?;
+    let objects = document.descendants()
+        .filter(|node| node.has_tag_name("object"))
+        .filter(|node| !node.ancestors().any(|ancestor| ancestor.has_tag_name("tileset")))
+        .map(|node| {
+            let components = node.children()
+                .filter(|child| child.has_tag_name("properties"))
+                .flat_map(|properties| properties.children())
+                .filter(|property| property.attribute("type") == Some("class"))
+                .filter_map(|property| {
+                    let fields = property.children()
+                        .filter(|child| child.has_tag_name("properties"))
+                        .flat_map(|properties| properties.children())
+                        .filter(|field| field.has_tag_name("property"))
+                        .filter_map(|field| Some((
+                            field.attribute("name")?.to_string(),
+                            field.attribute("value").unwrap_or_default().to_string(),
+                        )))
+                        .collect();
+                    Some((property.attribute("propertytype")?.to_string(), fields))
+                })
+                .collect();
+            MapObject {
+                id: node.attribute("id").and_then(|id| id.parse().ok()).unwrap_or_default(),
+                components,
+            }
+        })
+        .collect();
+    Ok(objects)
+}
+
+/// Suffix of the copies of maps with their tilesets embedded, which the game
+/// loads. They share the name of the map they copy.
+pub const EMBEDDED_SUFFIX: &str = "_emb";
+
+/// Leaves out the maps that have an embedded copy, so each broken object is
+/// reported once, for the copy the game loads.
+pub fn loaded_maps(maps: Vec<(String, PathBuf)>) -> Vec<(String, PathBuf)> {
+    let names: BTreeSet<String> = maps.iter().map(|(name, _)| name.clone()).collect();
+    maps.into_iter()
+        .filter(|(name, _)| !names.contains(&format!("{}{}", name, EMBEDDED_SUFFIX)))
+        .collect()
+}
+
+/// The `.tmx` maps in `directory` as `(name, path)`, sorted by name. Maps are
+/// named by their file name without the extension, as exits refer to them.
+pub fn maps_in(directory: &Path) -> std::io::Result<Vec<(String, PathBuf)>> {
+    let mut maps: Vec<(String, PathBuf)> = std::fs::read_dir(directory)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "tmx"))
+        .filter_map(|path| Some((path.file_stem()?.to_str()?.to_string(), path)))
+        .collect();
+    maps.sort();
+    Ok(maps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_embedded_copies_are_loaded() {
+        let maps = ["cave", "clearing", "clearing_emb", "road_emb"]
+            .map(|name| (name.to_string(), PathBuf::from(format!("{}.tmx", name))));
+        let names: Vec<String> = loaded_maps(maps.to_vec()).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, ["cave", "clearing_emb", "road_emb"]);
+    }
+}
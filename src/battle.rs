@@ -0,0 +1,619 @@
+pub mod capture;
+pub mod damage;
+pub mod engine;
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::GameState;
+use crate::control::GameControl;
+use crate::control::GameControlEvent;
+use crate::inventory::Inventory;
+use crate::inventory::CAPTURE_ORB;
+use crate::inventory::CAPTURE_ORB_BONUS;
+use crate::mob::TriggerEvent;
+use crate::party::xp_reward;
+use crate::party::DaemonInstance;
+use crate::party::LevelUpEvent;
+use crate::party::LearnResult;
+use crate::party::Party;
+use crate::rng::GameRng;
+use crate::species::SpeciesRegistry;
+use crate::state_stack::StateStack;
+use crate::storage::DaemonStorage;
+
+use engine::*;
+
+pub struct BattlePlugin;
+
+impl Plugin for BattlePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(Update, (
+            start_battle,
+        ).run_if(in_state(GameState::Playing)))
+        .add_systems(Update, (
+            battle_message_control,
+            battle_cancel_control,
+            battle_menu_control,
+            end_battle,
+        ).chain().run_if(in_state(GameState::Battle)))
+        .init_resource::<CurrentBattle>()
+        .init_resource::<OpponentDaemons>()
+        .init_resource::<BattleMessages>()
+        .init_resource::<BattlePhase>()
+        .add_event::<StartBattleEvent>()
+        .add_event::<BattleEndEvent>();
+    }
+}
+
+#[derive(Reflect, Debug, Clone, Deserialize)]
+pub struct DaemonSpec {
+    pub species: String,
+    pub level: u8,
+}
+
+#[derive(Event, Debug)]
+pub struct StartBattleEvent {
+    pub kind: BattleKind,
+    pub opponents: Vec<DaemonSpec>,
+    /// Name of the trainer, if any, for the opening message.
+    pub opponent_name: Option<String>,
+}
+
+#[derive(Event, Debug)]
+pub struct BattleEndEvent {
+    pub outcome: BattleOutcome,
+}
+
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct CurrentBattle(pub Option<Battle>);
+
+/// The opponent's daemons as full instances, so a captured one keeps its
+/// individual values and moves.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct OpponentDaemons(pub Vec<DaemonInstance>);
+
+#[derive(Resource, Default, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BattlePhase {
+    /// Playing back messages; Interact advances to the next one.
+    #[default]
+    Messages,
+    SelectAction,
+    SelectMove,
+    Finished,
+}
+
+/// Which daemon is out on each side and how much HP everyone had at a given
+/// point of the turn, so the HP display keeps pace with the messages.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BattleView {
+    pub player_active: usize,
+    pub opponent_active: usize,
+    pub player_hp: Vec<u16>,
+    pub opponent_hp: Vec<u16>,
+}
+
+impl BattleView {
+    fn of(battle: &Battle) -> Self {
+        BattleView {
+            player_active: battle.player.active,
+            opponent_active: battle.opponent.active,
+            player_hp: battle.player.daemons.iter().map(|d| d.hp).collect(),
+            opponent_hp: battle.opponent.daemons.iter().map(|d| d.hp).collect(),
+        }
+    }
+
+    fn apply(&mut self, log: &BattleLog) {
+        match log {
+            BattleLog::SentOut { side: Side::Player, index, .. } => self.player_active = *index,
+            BattleLog::SentOut { side: Side::Opponent, index, .. } => self.opponent_active = *index,
+            BattleLog::Damaged { side: Side::Player, amount } => {
+                let hp = &mut self.player_hp[self.player_active];
+                *hp = hp.saturating_sub(*amount);
+            }
+            BattleLog::Damaged { side: Side::Opponent, amount } => {
+                let hp = &mut self.opponent_hp[self.opponent_active];
+                *hp = hp.saturating_sub(*amount);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BattleMessage {
+    pub text: String,
+    pub view: BattleView,
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct BattleMessages {
+    queue: VecDeque<BattleMessage>,
+    last_view: BattleView,
+}
+
+impl BattleMessages {
+    pub fn current(&self) -> Option<&BattleMessage> {
+        self.queue.front()
+    }
+
+    /// The state to display: the one belonging to the current message, or
+    /// the last one shown once the queue has run dry.
+    pub fn view(&self) -> &BattleView {
+        self.current()
+            .map(|message| &message.view)
+            .unwrap_or(&self.last_view)
+    }
+
+    fn reset(&mut self, view: BattleView) {
+        self.queue.clear();
+        self.last_view = view;
+    }
+
+    fn push_text(&mut self, text: impl Into<String>) {
+        let view = self.queue.back()
+            .map(|message| message.view.clone())
+            .unwrap_or_else(|| self.last_view.clone());
+        self.queue.push_back(BattleMessage {
+            text: text.into(),
+            view,
+        });
+    }
+
+    /// Queues a message for each log entry, starting from `view`, and returns
+    /// the view after the last entry.
+    ///
+    /// Entries without text (such as damage) update the view of the message
+    /// before them instead.
+    fn push_log(&mut self, log: &[BattleLog], mut view: BattleView) -> BattleView {
+        for entry in log {
+            view.apply(entry);
+            match describe(entry) {
+                Some(text) => self.queue.push_back(BattleMessage {
+                    text,
+                    view: view.clone(),
+                }),
+                None => match self.queue.back_mut() {
+                    Some(message) => message.view = view.clone(),
+                    None => self.last_view = view.clone(),
+                },
+            }
+        }
+        view
+    }
+
+    fn advance(&mut self) {
+        if let Some(message) = self.queue.pop_front() {
+            self.last_view = message.view;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+fn describe(log: &BattleLog) -> Option<String> {
+    let text = match log {
+        BattleLog::SentOut { side: Side::Player, name, .. } => format!("Go, {}!", name),
+        BattleLog::SentOut { side: Side::Opponent, name, .. } => format!("Foe sent out {}!", name),
+        BattleLog::UsedMove { side: Side::Player, name, move_name } => {
+            format!("{} used {}!", name, move_name)
+        }
+        BattleLog::UsedMove { side: Side::Opponent, name, move_name } => {
+            format!("Foe {} used {}!", name, move_name)
+        }
+        BattleLog::NoPp { name, .. } => format!("{} has no PP left for that move!", name),
+        BattleLog::Missed { name, .. } => format!("{}'s attack missed!", name),
+        BattleLog::Damaged { .. } => return None,
+        BattleLog::CriticalHit => "A critical hit!".to_string(),
+        BattleLog::SuperEffective => "It's super effective!".to_string(),
+        BattleLog::NotVeryEffective => "It's not very effective...".to_string(),
+        BattleLog::NoEffect { name } => format!("It doesn't affect {}...", name),
+        BattleLog::Fainted { side: Side::Player, name, .. } => format!("{} fainted!", name),
+        BattleLog::Fainted { side: Side::Opponent, name, .. } => format!("Foe {} fainted!", name),
+        BattleLog::CannotFlee => "There's no running from a trainer battle!".to_string(),
+        BattleLog::FleeFailed => "Couldn't get away!".to_string(),
+        BattleLog::OrbThrown => "You threw a capture orb!".to_string(),
+        BattleLog::OrbShook => "The orb wobbles...".to_string(),
+        BattleLog::Captured { name, .. } => format!("Gotcha! {} was caught!", name),
+        BattleLog::BrokeFree { name } => format!("Oh no! {} broke free!", name),
+        BattleLog::CannotCapture => "You can't capture a trainer's daemon!".to_string(),
+        BattleLog::Ended(BattleOutcome::Captured) => return None,
+        BattleLog::Ended(BattleOutcome::Won) => "You won!".to_string(),
+        BattleLog::Ended(BattleOutcome::Lost) => "You have no daemons left to fight!".to_string(),
+        BattleLog::Ended(BattleOutcome::Fled) => "Got away safely!".to_string(),
+    };
+    Some(text)
+}
+
+/// Menu elements in the battle screen that pick a top level action.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BattleMenuAction {
+    Fight,
+    Orb,
+    Run,
+    Back,
+}
+
+/// Menu element that uses the move in the given slot of the active daemon.
+#[derive(Component, Debug, Clone, Copy, Deref)]
+pub struct BattleMoveSlot(pub usize);
+
+/// Combatants for every party member, in party order so indices line up.
+fn party_combatants(party: &Party, registry: &SpeciesRegistry) -> Option<Vec<Combatant>> {
+    party.members().iter()
+        .map(|daemon| match registry.species.get(&daemon.species) {
+            Some(species) => Some(Combatant::from_instance(daemon, species)),
+            None => {
+                warn!("Unknown species in party: {}", daemon.species);
+                None
+            }
+        })
+        .collect()
+}
+
+fn opponent_instances(
+    specs: &[DaemonSpec],
+    registry: &SpeciesRegistry,
+    rng: &mut GameRng,
+) -> Vec<(DaemonInstance, Combatant)> {
+    specs.iter()
+        .filter_map(|spec| match registry.species.get(&spec.species) {
+            Some(species) => {
+                let daemon = DaemonInstance::new(species, spec.level, &registry.moves, &mut **rng);
+                let combatant = Combatant::from_instance(&daemon, species);
+                Some((daemon, combatant))
+            }
+            None => {
+                warn!("Unknown species in battle: {}", spec.species);
+                None
+            }
+        })
+        .collect()
+}
+
+/// Moves the opponent at `index` into the party, or into storage if the party
+/// is full, and returns the message to show.
+fn keep_captured(
+    battle: &Battle,
+    index: usize,
+    opponents: &mut OpponentDaemons,
+    party: &mut Party,
+    storage: &mut DaemonStorage,
+) -> Option<String> {
+    let combatant = battle.opponent.daemons.get(index)?;
+    let mut daemon = opponents.get(index)?.clone();
+    daemon.sync_with(combatant);
+    // A daemon with nowhere to go is let go without using up a capture number.
+    if party.is_full() && storage.is_full() {
+        return Some(format!("There's no room left for {}...", combatant.name));
+    }
+    storage.record_capture(&mut daemon);
+
+    let daemon = match party.add(daemon) {
+        Ok(()) => return None,
+        Err(daemon) => daemon,
+    };
+    match storage.deposit(daemon) {
+        Ok(box_index) => Some(format!(
+            "{} was sent to {}.",
+            combatant.name, storage.boxes[box_index].name,
+        )),
+        Err(_) => Some(format!("There's no room left for {}...", combatant.name)),
+    }
+}
+
+/// Gives experience for the opponent at `defeated` to the player's daemon at
+/// `recipient`, keeping the party and the battle in step, and returns the
+/// messages to show.
+fn award_xp(
+    battle: &mut Battle,
+    recipient: usize,
+    defeated: usize,
+    party: &mut Party,
+    registry: &SpeciesRegistry,
+    level_up_events: &mut EventWriter<LevelUpEvent>,
+) -> Vec<String> {
+    let mut messages = Vec::new();
+    let trainer_owned = battle.kind == BattleKind::Trainer;
+    let (Some(combatant), Some(foe), Some(daemon)) = (
+        battle.player.daemons.get_mut(recipient),
+        battle.opponent.daemons.get(defeated),
+        party.get_mut(recipient),
+    ) else {
+        return messages;
+    };
+    let (Some(species), Some(foe_species)) = (
+        registry.species.get(&daemon.species),
+        registry.species.get(&foe.species),
+    ) else {
+        return messages;
+    };
+    if combatant.is_fainted() {
+        return messages;
+    }
+
+    daemon.sync_with(combatant);
+    let xp = xp_reward(foe_species, foe.level, trainer_owned);
+    messages.push(format!("{} gained {} XP!", combatant.name, xp));
+
+    for level in daemon.gain_xp(xp, species) {
+        messages.push(format!("{} grew to Lv{}!", combatant.name, level));
+        level_up_events.send(LevelUpEvent { index: recipient, level });
+        for move_id in DaemonInstance::moves_learned_at(species, level) {
+            let Some(data) = registry.moves.get(move_id) else {
+                continue;
+            };
+            match daemon.learn_move(move_id, data) {
+                LearnResult::Learned => {
+                    messages.push(format!("{} learned {}!", combatant.name, data.name));
+                }
+                LearnResult::SlotsFull => {
+                    messages.push(format!(
+                        "{} wants to learn {}, but already knows {} moves.",
+                        combatant.name, data.name, MAX_MOVES,
+                    ));
+                }
+                LearnResult::AlreadyKnown => {}
+            }
+        }
+    }
+    // Rebuilt so the new level, stats and moves take effect for the rest of the battle.
+    *combatant = Combatant::from_instance(daemon, species);
+    messages
+}
+
+fn start_battle(
+    mut events: EventReader<StartBattleEvent>,
+    registry: Res<SpeciesRegistry>,
+    party: Res<Party>,
+    mut rng: ResMut<GameRng>,
+    mut current_battle: ResMut<CurrentBattle>,
+    mut opponent_daemons: ResMut<OpponentDaemons>,
+    mut messages: ResMut<BattleMessages>,
+    mut phase: ResMut<BattlePhase>,
+    mut end_events: EventWriter<BattleEndEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+
+    let player = party_combatants(&party, &registry).unwrap_or_default();
+    let (daemons, opponent): (Vec<_>, Vec<_>) =
+        opponent_instances(&event.opponents, &registry, &mut rng).into_iter().unzip();
+    if player.iter().all(Combatant::is_fainted) || opponent.is_empty() {
+        warn!("Battle skipped: one side has nothing to fight with");
+        end_events.send(BattleEndEvent {
+            outcome: BattleOutcome::Fled,
+        });
+        return;
+    }
+
+    let battle = Battle::new(event.kind, player, opponent);
+    messages.reset(BattleView::of(&battle));
+    let opening = battle.opening_log();
+    match battle.kind {
+        BattleKind::Wild => {
+            messages.push_text(format!("A wild {} appeared!", battle.opponent.active().name));
+            messages.push_log(&opening[1..], BattleView::of(&battle));
+        }
+        BattleKind::Trainer => {
+            messages.push_text(match &event.opponent_name {
+                Some(name) => format!("{} wants to battle!", name),
+                None => "You are challenged to a battle!".to_string(),
+            });
+            messages.push_log(&opening, BattleView::of(&battle));
+        }
+    }
+
+    *current_battle = CurrentBattle(Some(battle));
+    *opponent_daemons = OpponentDaemons(daemons);
+    *phase = BattlePhase::Messages;
+    next_state.set(state_stack.push(GameState::Battle));
+}
+
+fn battle_message_control(
+    state: Res<State<GameState>>,
+    mut control_events: EventReader<GameControlEvent>,
+    mut messages: ResMut<BattleMessages>,
+    mut phase: ResMut<BattlePhase>,
+    current_battle: Res<CurrentBattle>,
+) {
+    if *phase != BattlePhase::Messages {
+        return;
+    }
+    if control_events.read()
+        .filter(|e| e.just_pressed())
+        .any(|e| e.control == GameControl::Interact)
+        && !state.is_changed()
+    {
+        messages.advance();
+    }
+    if messages.is_empty() {
+        let finished = current_battle.0.as_ref()
+            .map_or(true, |battle| battle.outcome.is_some());
+        *phase = if finished {
+            BattlePhase::Finished
+        } else {
+            BattlePhase::SelectAction
+        };
+    }
+}
+
+/// Cancel backs out of the move list, like its Back entry.
+fn battle_cancel_control(
+    mut control_events: EventReader<GameControlEvent>,
+    mut phase: ResMut<BattlePhase>,
+) {
+    let cancelled = control_events.read()
+        .filter(|e| e.just_pressed())
+        .any(|e| e.control == GameControl::Cancel);
+    if cancelled && *phase == BattlePhase::SelectMove {
+        *phase = BattlePhase::SelectAction;
+    }
+}
+
+fn battle_menu_control(
+    mut events: EventReader<TriggerEvent>,
+    action_query: Query<&BattleMenuAction>,
+    move_slot_query: Query<&BattleMoveSlot>,
+    mut current_battle: ResMut<CurrentBattle>,
+    mut messages: ResMut<BattleMessages>,
+    mut phase: ResMut<BattlePhase>,
+    mut rng: ResMut<GameRng>,
+    registry: Res<SpeciesRegistry>,
+    mut party: ResMut<Party>,
+    mut storage: ResMut<DaemonStorage>,
+    mut inventory: ResMut<Inventory>,
+    mut opponent_daemons: ResMut<OpponentDaemons>,
+    mut level_up_events: EventWriter<LevelUpEvent>,
+) {
+    for event in events.read() {
+        let Some(battle) = current_battle.0.as_mut() else {
+            return;
+        };
+        let player_action = match (*phase, action_query.get(event.triggered), move_slot_query.get(event.triggered)) {
+            (BattlePhase::SelectAction, Ok(BattleMenuAction::Fight), _) => {
+                *phase = BattlePhase::SelectMove;
+                continue;
+            }
+            (BattlePhase::SelectAction, Ok(BattleMenuAction::Orb), _) => {
+                if battle.kind == BattleKind::Wild && !inventory.take(CAPTURE_ORB) {
+                    messages.push_text("You don't have any capture orbs left!");
+                    *phase = BattlePhase::Messages;
+                    continue;
+                }
+                BattleAction::Throw(CAPTURE_ORB_BONUS)
+            }
+            (BattlePhase::SelectAction, Ok(BattleMenuAction::Run), _) => BattleAction::Run,
+            (BattlePhase::SelectMove, Ok(BattleMenuAction::Back), _) => {
+                *phase = BattlePhase::SelectAction;
+                continue;
+            }
+            (BattlePhase::SelectMove, _, Ok(slot)) => {
+                match battle.player.active().moves.get(**slot) {
+                    Some(move_slot) if move_slot.pp > 0 => BattleAction::Fight(**slot),
+                    _ => continue,
+                }
+            }
+            _ => continue,
+        };
+
+        let mut view = BattleView::of(battle);
+        let opponent_action = battle.choose_opponent_action(&mut **rng);
+        let log = battle.resolve_turn(player_action, opponent_action, &registry, &mut **rng);
+
+        // Experience is shown right after each faint, before the next daemon comes out.
+        let mut start = 0;
+        for (position, entry) in log.iter().enumerate() {
+            if let BattleLog::Fainted { side: Side::Opponent, index, .. } = entry {
+                view = messages.push_log(&log[start..=position], view);
+                start = position + 1;
+                for text in award_xp(
+                    battle,
+                    view.player_active,
+                    *index,
+                    &mut party,
+                    &registry,
+                    &mut level_up_events,
+                ) {
+                    messages.push_text(text);
+                }
+            }
+        }
+        messages.push_log(&log[start..], view);
+
+        for entry in &log {
+            if let BattleLog::Captured { index, .. } = entry {
+                let kept = keep_captured(battle, *index, &mut opponent_daemons, &mut party, &mut storage);
+                if let Some(text) = kept {
+                    messages.push_text(text);
+                }
+            }
+        }
+        *phase = BattlePhase::Messages;
+    }
+}
+
+fn end_battle(
+    mut phase: ResMut<BattlePhase>,
+    mut current_battle: ResMut<CurrentBattle>,
+    mut party: ResMut<Party>,
+    registry: Res<SpeciesRegistry>,
+    mut end_events: EventWriter<BattleEndEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+) {
+    if *phase != BattlePhase::Finished {
+        return;
+    }
+    if let Some(battle) = current_battle.take() {
+        for (daemon, combatant) in party.members_mut().iter_mut().zip(&battle.player.daemons) {
+            daemon.sync_with(combatant);
+        }
+        // There is nowhere to respawn yet, so a loss just patches the party up.
+        if battle.outcome == Some(BattleOutcome::Lost) {
+            party.heal_all(&registry);
+        }
+        end_events.send(BattleEndEvent {
+            outcome: battle.outcome.unwrap_or(BattleOutcome::Fled),
+        });
+    }
+    *phase = BattlePhase::default();
+    next_state.set(state_stack.back());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::party::PARTY_SIZE;
+    use crate::species::MoveList;
+    use crate::species::Species;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn cancel_backs_out_of_the_move_list() {
+        let mut app = TestApp::new();
+        app.app
+        .insert_resource(BattlePhase::SelectMove)
+        .add_systems(Update, battle_cancel_control);
+
+        app.press(GameControl::Cancel);
+        assert_eq!(*app.app.world().resource::<BattlePhase>(), BattlePhase::SelectAction);
+
+        app.press(GameControl::Cancel);
+        assert_eq!(*app.app.world().resource::<BattlePhase>(), BattlePhase::SelectAction);
+    }
+
+    #[test]
+    fn captures_with_no_room_keep_their_number() {
+        let species: Species = ron::from_str(include_str!("../assets/daemons/species/pebblit.species.ron")).unwrap();
+        let moves: MoveList = ron::from_str(include_str!("../assets/daemons/moves.moves.ron")).unwrap();
+        let daemon = DaemonInstance::new(&species, 5, &moves, &mut StdRng::seed_from_u64(7));
+        let combatant = Combatant::from_instance(&daemon, &species);
+        let battle = Battle::new(BattleKind::Wild, vec![combatant.clone()], vec![combatant]);
+        let mut opponents = OpponentDaemons(vec![daemon.clone()]);
+
+        let mut party = Party::default();
+        let mut storage = DaemonStorage::default();
+        for _ in 0..PARTY_SIZE {
+            party.add(daemon.clone()).unwrap();
+        }
+        while storage.deposit(daemon.clone()).is_ok() {}
+
+        let text = keep_captured(&battle, 0, &mut opponents, &mut party, &mut storage);
+        assert_eq!(text.as_deref(), Some("There's no room left for Pebblit..."));
+
+        party.remove(0);
+        assert_eq!(keep_captured(&battle, 0, &mut opponents, &mut party, &mut storage), None);
+        assert_eq!(party.members().last().unwrap().caught_order, 1);
+    }
+}
@@ -0,0 +1,128 @@
+//! The catch-rate formula.
+//!
+//! Like [`super::damage`], this is plain math with the RNG passed in, so it can
+//! be tuned and tested without a Bevy `App`.
+
+use rand::Rng;
+
+use crate::party::StatusCondition;
+
+/// Shake checks a capture has to pass; failing the first gives 0 shakes.
+pub const SHAKE_CHECKS: u8 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CaptureResult {
+    /// How many times the orb wobbles before the outcome, from 0 to 3.
+    pub shakes: u8,
+    pub caught: bool,
+}
+
+/// Everything about a capture attempt that is known before rolling.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CaptureInput {
+    /// Species catch rate, from 1 (hardest) to 255 (easiest).
+    pub catch_rate: u8,
+    pub max_hp: u16,
+    pub hp: u16,
+    pub status: StatusCondition,
+    /// Multiplier from the orb used.
+    pub orb_bonus: f32,
+}
+
+impl CaptureInput {
+    fn status_bonus(&self) -> f32 {
+        match self.status {
+            StatusCondition::Asleep | StatusCondition::Frozen => 2.0,
+            StatusCondition::Paralyzed | StatusCondition::Poisoned | StatusCondition::Burned => 1.5,
+            StatusCondition::Healthy => 1.0,
+        }
+    }
+
+    /// Modified catch rate: higher is easier, 255 or more always succeeds.
+    pub fn modified_rate(&self) -> f32 {
+        let max_hp = self.max_hp.max(1) as f32;
+        let hp = self.hp.min(self.max_hp) as f32;
+        (3.0 * max_hp - 2.0 * hp) * self.catch_rate as f32 * self.orb_bonus
+            / (3.0 * max_hp)
+            * self.status_bonus()
+    }
+
+    /// Chance out of 65536 of passing each shake check.
+    pub fn shake_threshold(&self) -> u32 {
+        let rate = self.modified_rate();
+        if rate <= 0.0 {
+            return 0;
+        }
+        if rate >= 255.0 {
+            return 65536;
+        }
+        (1_048_560.0 / (16_711_680.0 / rate).sqrt().sqrt()) as u32
+    }
+}
+
+/// Rolls a capture attempt.
+pub fn attempt_capture(input: &CaptureInput, rng: &mut impl Rng) -> CaptureResult {
+    let threshold = input.shake_threshold();
+    let passed = (0..SHAKE_CHECKS)
+        .take_while(|_| rng.gen_range(0..65536) < threshold)
+        .count() as u8;
+    CaptureResult {
+        shakes: passed.min(SHAKE_CHECKS - 1),
+        caught: passed == SHAKE_CHECKS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn input() -> CaptureInput {
+        CaptureInput {
+            catch_rate: 45,
+            max_hp: 100,
+            hp: 100,
+            status: StatusCondition::Healthy,
+            orb_bonus: 1.0,
+        }
+    }
+
+    #[test]
+    fn lower_hp_and_status_make_capture_easier() {
+        let full = input().modified_rate();
+        let low = CaptureInput { hp: 1, ..input() }.modified_rate();
+        let asleep = CaptureInput { status: StatusCondition::Asleep, ..input() }.modified_rate();
+        assert_eq!(full, 15.0);
+        assert!(low > full);
+        assert_eq!(asleep, 30.0);
+    }
+
+    #[test]
+    fn easy_catch_always_succeeds() {
+        let easy = CaptureInput { catch_rate: 255, hp: 1, orb_bonus: 2.0, ..input() };
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..50 {
+            assert_eq!(attempt_capture(&easy, &mut rng), CaptureResult { shakes: 3, caught: true });
+        }
+    }
+
+    #[test]
+    fn shakes_stay_in_range() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..500 {
+            let result = attempt_capture(&input(), &mut rng);
+            assert!(result.shakes <= 3);
+            assert!(!result.caught || result.shakes == 3);
+        }
+    }
+
+    #[test]
+    fn seeded_rng_is_deterministic() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let shakes: Vec<u8> = (0..8)
+            .map(|_| attempt_capture(&input(), &mut rng).shakes)
+            .collect();
+        assert_eq!(shakes, vec![2, 0, 0, 0, 1, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,216 @@
+//! Type effectiveness and the damage formula.
+//!
+//! Everything here is plain data and functions so numbers can be tuned and
+//! tested without a Bevy `App`. Randomness is always passed in by the caller.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+/// Multiplier applied when a move shares a type with its user.
+pub const STAB_MULTIPLIER: f32 = 1.5;
+
+/// Multiplier applied to critical hits.
+pub const CRITICAL_MULTIPLIER: f32 = 1.5;
+
+/// Chance of a critical hit, as one in this many.
+pub const CRITICAL_ODDS: u32 = 16;
+
+/// Lowest roll of the random spread, as a percentage of full damage.
+pub const MIN_SPREAD: u8 = 85;
+
+/// The known elemental types and how effective each is against the others,
+/// loaded from `daemons/types.types.ron`.
+///
+/// Matchups are keyed by attacking type, then defending type. Pairs that are
+/// not listed are neutral.
+#[derive(Asset, Reflect, Debug, Deserialize, Clone, Default)]
+pub struct TypeChart {
+    pub types: Vec<String>,
+    #[serde(default)]
+    pub matchups: HashMap<String, HashMap<String, f32>>,
+}
+
+impl TypeChart {
+    pub fn contains(&self, element: &str) -> bool {
+        self.types.iter().any(|known| known == element)
+    }
+
+    /// Multiplier for a single attacking type against a single defending type.
+    pub fn matchup(&self, attack: &str, defend: &str) -> f32 {
+        self.matchups.get(attack)
+            .and_then(|row| row.get(defend))
+            .copied()
+            .unwrap_or(1.0)
+    }
+
+    /// Combined multiplier against every type of the defender.
+    pub fn effectiveness<S: AsRef<str>>(&self, attack: &str, defender_types: &[S]) -> f32 {
+        defender_types.iter()
+            .map(|defend| self.matchup(attack, defend.as_ref()))
+            .product()
+    }
+}
+
+/// Everything about an attack that is known before any dice are rolled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DamageInput {
+    pub level: u8,
+    pub power: u16,
+    pub attack: u16,
+    pub defense: u16,
+    pub stab: bool,
+    pub effectiveness: f32,
+}
+
+/// The random parts of a hit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DamageRoll {
+    pub critical: bool,
+    /// Percentage of full damage dealt, from [`MIN_SPREAD`] to 100.
+    pub spread: u8,
+}
+
+impl DamageRoll {
+    pub fn random(rng: &mut impl Rng) -> Self {
+        DamageRoll {
+            critical: rng.gen_ratio(1, CRITICAL_ODDS),
+            spread: rng.gen_range(MIN_SPREAD..=100),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DamageResult {
+    pub amount: u16,
+    pub critical: bool,
+    pub effectiveness: f32,
+}
+
+/// Rolls for a critical hit and spread, then applies [`damage_with_roll`].
+pub fn calculate_damage(input: &DamageInput, rng: &mut impl Rng) -> DamageResult {
+    damage_with_roll(input, DamageRoll::random(rng))
+}
+
+/// The damage formula with the random parts already decided.
+///
+/// Immune matchups deal nothing; anything else deals at least 1.
+pub fn damage_with_roll(input: &DamageInput, roll: DamageRoll) -> DamageResult {
+    let result = |amount| DamageResult {
+        amount,
+        critical: roll.critical,
+        effectiveness: input.effectiveness,
+    };
+    if input.effectiveness <= 0.0 || input.power == 0 {
+        return result(0);
+    }
+
+    let level = input.level as f32;
+    let base = ((2.0 * level / 5.0 + 2.0).floor()
+        * input.power as f32
+        * input.attack as f32
+        / input.defense.max(1) as f32
+        / 50.0).floor() + 2.0;
+
+    let mut amount = base;
+    if roll.critical {
+        amount = (amount * CRITICAL_MULTIPLIER).floor();
+    }
+    amount = (amount * roll.spread as f32 / 100.0).floor();
+    if input.stab {
+        amount = (amount * STAB_MULTIPLIER).floor();
+    }
+    amount = (amount * input.effectiveness).floor();
+
+    result(amount.clamp(1.0, u16::MAX as f32) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn chart() -> TypeChart {
+        ron::from_str(r#"
+            TypeChart(
+                types: ["normal", "fire", "water", "grass"],
+                matchups: {
+                    "fire": { "grass": 2.0, "water": 0.5, "fire": 0.5 },
+                    "water": { "fire": 2.0, "grass": 0.5 },
+                    "normal": { "ghost": 0.0 },
+                },
+            )
+        "#).unwrap()
+    }
+
+    fn input() -> DamageInput {
+        DamageInput {
+            level: 10,
+            power: 40,
+            attack: 20,
+            defense: 15,
+            stab: false,
+            effectiveness: 1.0,
+        }
+    }
+
+    const PLAIN: DamageRoll = DamageRoll { critical: false, spread: 100 };
+
+    #[test]
+    fn unlisted_matchups_are_neutral() {
+        let chart = chart();
+        assert_eq!(chart.matchup("grass", "normal"), 1.0);
+        assert_eq!(chart.matchup("fire", "grass"), 2.0);
+    }
+
+    #[test]
+    fn effectiveness_multiplies_across_defender_types() {
+        let chart = chart();
+        assert_eq!(chart.effectiveness("fire", &["grass", "water"]), 1.0);
+        assert_eq!(chart.effectiveness("water", &["fire", "fire"]), 4.0);
+        assert_eq!(chart.effectiveness("normal", &["ghost", "fire"]), 0.0);
+    }
+
+    #[test]
+    fn base_damage() {
+        // floor(floor(2 * 10 / 5 + 2) * 40 * 20 / 15 / 50) + 2 = 8
+        assert_eq!(damage_with_roll(&input(), PLAIN).amount, 8);
+    }
+
+    #[test]
+    fn modifiers_apply_in_order() {
+        let input = DamageInput { stab: true, effectiveness: 2.0, ..input() };
+        let roll = DamageRoll { critical: true, spread: 85 };
+        // 8 -> crit 12 -> spread 10 -> stab 15 -> super effective 30
+        let result = damage_with_roll(&input, roll);
+        assert_eq!(result.amount, 30);
+        assert!(result.critical);
+    }
+
+    #[test]
+    fn immune_deals_nothing_and_resisted_deals_at_least_one() {
+        let immune = DamageInput { effectiveness: 0.0, ..input() };
+        assert_eq!(damage_with_roll(&immune, PLAIN).amount, 0);
+
+        let weak = DamageInput { power: 1, attack: 1, defense: 255, effectiveness: 0.25, ..input() };
+        assert_eq!(damage_with_roll(&weak, PLAIN).amount, 1);
+    }
+
+    #[test]
+    fn seeded_rng_is_deterministic() {
+        let input = DamageInput { level: 50, power: 80, attack: 120, defense: 90, ..input() };
+        let rolls: Vec<u16> = (0..5)
+            .map(|_| calculate_damage(&input, &mut StdRng::seed_from_u64(7)).amount)
+            .collect();
+        assert!(rolls.windows(2).all(|pair| pair[0] == pair[1]));
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let amounts: Vec<u16> = (0..4)
+            .map(|_| calculate_damage(&input, &mut rng).amount)
+            .collect();
+        assert_eq!(amounts, vec![62, 62, 45, 48]);
+    }
+}
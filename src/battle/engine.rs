@@ -0,0 +1,631 @@
+//! Headless battle rules.
+//!
+//! Nothing in here touches the ECS: a [`Battle`] is plain data that is advanced one
+//! turn at a time with [`Battle::resolve_turn`], which returns a [`BattleLog`] for the
+//! presentation layer to play back.
+
+use bevy::log::warn;
+use bevy::reflect::Reflect;
+use rand::Rng;
+use rand::seq::IteratorRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::party::{DaemonInstance, StatSpread, StatusCondition};
+use crate::species::{BaseStats, MoveCategory, Species, SpeciesRegistry};
+
+use super::capture::{attempt_capture, CaptureInput};
+use super::damage::{calculate_damage, DamageInput};
+
+/// Number of moves a daemon can know at once.
+pub const MAX_MOVES: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Stats {
+    pub hp: u16,
+    pub attack: u16,
+    pub defense: u16,
+    pub sp_attack: u16,
+    pub sp_defense: u16,
+    pub speed: u16,
+}
+
+impl Stats {
+    pub fn calculate(base: &BaseStats, ivs: &StatSpread, evs: &StatSpread, level: u8) -> Self {
+        let level = level as u32;
+        let raw = |base: u16, iv: u16, ev: u16| {
+            (2 * base as u32 + iv as u32 + ev as u32 / 4) * level / 100
+        };
+        let stat = |base, iv, ev| (raw(base, iv, ev) + 5) as u16;
+        Stats {
+            hp: (raw(base.hp, ivs.hp, evs.hp) + level + 10) as u16,
+            attack: stat(base.attack, ivs.attack, evs.attack),
+            defense: stat(base.defense, ivs.defense, evs.defense),
+            sp_attack: stat(base.sp_attack, ivs.sp_attack, evs.sp_attack),
+            sp_defense: stat(base.sp_defense, ivs.sp_defense, evs.sp_defense),
+            speed: stat(base.speed, ivs.speed, evs.speed),
+        }
+    }
+}
+
+#[derive(Reflect, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MoveSlot {
+    pub id: String,
+    pub pp: u8,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Combatant {
+    pub species: String,
+    pub name: String,
+    pub level: u8,
+    pub types: Vec<String>,
+    pub stats: Stats,
+    pub hp: u16,
+    pub moves: Vec<MoveSlot>,
+    pub status: StatusCondition,
+}
+
+impl Combatant {
+    /// A daemon as it enters battle, with the HP and PP it has left.
+    pub fn from_instance(instance: &DaemonInstance, species: &Species) -> Self {
+        let stats = instance.stats(species);
+        Combatant {
+            species: species.id.clone(),
+            name: instance.name(species).to_string(),
+            level: instance.level,
+            types: species.types.clone(),
+            stats,
+            hp: instance.hp.min(stats.hp),
+            moves: instance.moves.clone(),
+            status: instance.status,
+        }
+    }
+
+    pub fn is_fainted(&self) -> bool {
+        self.hp == 0
+    }
+
+    pub fn take_damage(&mut self, amount: u16) {
+        self.hp = self.hp.saturating_sub(amount);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Player,
+    Opponent,
+}
+
+impl Side {
+    pub fn other(self) -> Side {
+        match self {
+            Side::Player => Side::Opponent,
+            Side::Opponent => Side::Player,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BattleKind {
+    Wild,
+    Trainer,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BattleAction {
+    /// Use the move in the given slot of the active daemon.
+    Fight(usize),
+    /// Swap the active daemon for the one at the given party index.
+    Switch(usize),
+    /// Throw a capture orb with the given catch-rate bonus at the opponent.
+    Throw(f32),
+    Run,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BattleOutcome {
+    Won,
+    Lost,
+    Fled,
+    Captured,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BattleLog {
+    SentOut { side: Side, index: usize, name: String },
+    UsedMove { side: Side, name: String, move_name: String },
+    NoPp { side: Side, name: String },
+    Missed { side: Side, name: String },
+    Damaged { side: Side, amount: u16 },
+    CriticalHit,
+    SuperEffective,
+    NotVeryEffective,
+    NoEffect { name: String },
+    Fainted { side: Side, index: usize, name: String },
+    CannotFlee,
+    FleeFailed,
+    OrbThrown,
+    OrbShook,
+    Captured { index: usize, name: String },
+    BrokeFree { name: String },
+    CannotCapture,
+    Ended(BattleOutcome),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BattleSide {
+    pub daemons: Vec<Combatant>,
+    pub active: usize,
+}
+
+impl BattleSide {
+    pub fn new(daemons: Vec<Combatant>) -> Self {
+        let active = daemons.iter()
+            .position(|daemon| !daemon.is_fainted())
+            .unwrap_or(0);
+        BattleSide { daemons, active }
+    }
+
+    pub fn active(&self) -> &Combatant {
+        &self.daemons[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Combatant {
+        &mut self.daemons[self.active]
+    }
+
+    pub fn next_healthy(&self) -> Option<usize> {
+        self.daemons.iter().position(|daemon| !daemon.is_fainted())
+    }
+
+    pub fn all_fainted(&self) -> bool {
+        self.daemons.iter().all(Combatant::is_fainted)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Battle {
+    pub kind: BattleKind,
+    pub player: BattleSide,
+    pub opponent: BattleSide,
+    pub turn: u32,
+    pub outcome: Option<BattleOutcome>,
+    flee_attempts: u32,
+}
+
+impl Battle {
+    pub fn new(kind: BattleKind, player: Vec<Combatant>, opponent: Vec<Combatant>) -> Self {
+        Battle {
+            kind,
+            player: BattleSide::new(player),
+            opponent: BattleSide::new(opponent),
+            turn: 0,
+            outcome: None,
+            flee_attempts: 0,
+        }
+    }
+
+    pub fn side(&self, side: Side) -> &BattleSide {
+        match side {
+            Side::Player => &self.player,
+            Side::Opponent => &self.opponent,
+        }
+    }
+
+    pub fn side_mut(&mut self, side: Side) -> &mut BattleSide {
+        match side {
+            Side::Player => &mut self.player,
+            Side::Opponent => &mut self.opponent,
+        }
+    }
+
+    /// Log for the daemons that are out when the battle begins.
+    pub fn opening_log(&self) -> Vec<BattleLog> {
+        vec![
+            BattleLog::SentOut {
+                side: Side::Opponent,
+                index: self.opponent.active,
+                name: self.opponent.active().name.clone(),
+            },
+            BattleLog::SentOut {
+                side: Side::Player,
+                index: self.player.active,
+                name: self.player.active().name.clone(),
+            },
+        ]
+    }
+
+    /// Picks a random move that still has PP for the opponent's active daemon.
+    pub fn choose_opponent_action(&self, rng: &mut impl Rng) -> BattleAction {
+        let slot = self.opponent.active().moves.iter()
+            .enumerate()
+            .filter(|(_, slot)| slot.pp > 0)
+            .map(|(index, _)| index)
+            .choose(rng)
+            .unwrap_or(0);
+        BattleAction::Fight(slot)
+    }
+
+    /// Runs a full turn and returns what happened, in order.
+    pub fn resolve_turn(
+        &mut self,
+        player_action: BattleAction,
+        opponent_action: BattleAction,
+        data: &SpeciesRegistry,
+        rng: &mut impl Rng,
+    ) -> Vec<BattleLog> {
+        let mut log = Vec::new();
+        if self.outcome.is_some() {
+            return log;
+        }
+        self.turn += 1;
+
+        if player_action == BattleAction::Run {
+            match self.kind {
+                BattleKind::Trainer => {
+                    log.push(BattleLog::CannotFlee);
+                    return log;
+                }
+                BattleKind::Wild => {
+                    if self.try_flee(rng) {
+                        self.finish(BattleOutcome::Fled, &mut log);
+                        return log;
+                    }
+                    log.push(BattleLog::FleeFailed);
+                }
+            }
+        }
+
+        if let BattleAction::Throw(bonus) = player_action {
+            if self.kind == BattleKind::Trainer {
+                log.push(BattleLog::CannotCapture);
+                return log;
+            }
+            if self.throw_orb(bonus, data, rng, &mut log) {
+                return log;
+            }
+        }
+
+        let mut actions = vec![
+            (Side::Player, player_action),
+            (Side::Opponent, opponent_action),
+        ];
+        actions.retain(|(_, action)| {
+            !matches!(action, BattleAction::Run | BattleAction::Throw(_))
+        });
+
+        // Switches always go first, then moves by priority and speed.
+        let first_is_player = match (&actions.first(), &actions.get(1)) {
+            (Some((_, first)), Some((_, second))) => {
+                let order = |side: Side, action: &BattleAction| match action {
+                    BattleAction::Switch(_) => (1, i8::MAX, 0),
+                    BattleAction::Fight(slot) => {
+                        let priority = self.side(side).active().moves.get(*slot)
+                            .and_then(|slot| data.moves.get(&slot.id))
+                            .map(|data| data.priority)
+                            .unwrap_or(0);
+                        (0, priority, self.side(side).active().stats.speed)
+                    }
+                    BattleAction::Throw(_) | BattleAction::Run => (0, 0, 0),
+                };
+                let player_order = order(Side::Player, first);
+                let opponent_order = order(Side::Opponent, second);
+                if player_order == opponent_order {
+                    rng.gen_bool(0.5)
+                } else {
+                    player_order > opponent_order
+                }
+            }
+            _ => true,
+        };
+        if !first_is_player {
+            actions.reverse();
+        }
+
+        for (side, action) in actions {
+            match action {
+                BattleAction::Switch(index) => self.switch(side, index, &mut log),
+                BattleAction::Fight(slot) => {
+                    if self.side(side).active().is_fainted() {
+                        continue;
+                    }
+                    self.use_move(side, slot, data, rng, &mut log);
+                }
+                BattleAction::Throw(_) | BattleAction::Run => {}
+            }
+            if self.outcome.is_some() {
+                return log;
+            }
+        }
+
+        for side in [Side::Player, Side::Opponent] {
+            if self.side(side).active().is_fainted() {
+                if let Some(index) = self.side(side).next_healthy() {
+                    self.switch(side, index, &mut log);
+                }
+            }
+        }
+
+        log
+    }
+
+    fn switch(&mut self, side: Side, index: usize, log: &mut Vec<BattleLog>) {
+        let battle_side = self.side_mut(side);
+        if index >= battle_side.daemons.len() || battle_side.daemons[index].is_fainted() {
+            return;
+        }
+        battle_side.active = index;
+        log.push(BattleLog::SentOut {
+            side,
+            index,
+            name: battle_side.active().name.clone(),
+        });
+    }
+
+    fn use_move(
+        &mut self,
+        side: Side,
+        slot: usize,
+        data: &SpeciesRegistry,
+        rng: &mut impl Rng,
+        log: &mut Vec<BattleLog>,
+    ) {
+        let attacker = self.side_mut(side).active_mut();
+        let name = attacker.name.clone();
+        let Some(move_slot) = attacker.moves.get_mut(slot) else {
+            return;
+        };
+        let Some(move_data) = data.moves.get(&move_slot.id) else {
+            warn!("{} knows unknown move `{}`", name, move_slot.id);
+            return;
+        };
+        if move_slot.pp == 0 {
+            log.push(BattleLog::NoPp { side, name });
+            return;
+        }
+        move_slot.pp -= 1;
+
+        log.push(BattleLog::UsedMove {
+            side,
+            name: name.clone(),
+            move_name: move_data.name.clone(),
+        });
+
+        if rng.gen_range(0..100) >= move_data.accuracy as u32 {
+            log.push(BattleLog::Missed { side, name });
+            return;
+        }
+        if move_data.category == MoveCategory::Status {
+            return;
+        }
+
+        let attacker = self.side(side).active();
+        let defender = self.side(side.other()).active();
+        let (attack, defense) = match move_data.category {
+            MoveCategory::Special => (attacker.stats.sp_attack, defender.stats.sp_defense),
+            _ => (attacker.stats.attack, defender.stats.defense),
+        };
+        let result = calculate_damage(&DamageInput {
+            level: attacker.level,
+            power: move_data.power,
+            attack,
+            defense,
+            stab: attacker.types.contains(&move_data.element),
+            effectiveness: data.types.effectiveness(&move_data.element, &defender.types),
+        }, rng);
+
+        if result.amount == 0 {
+            log.push(BattleLog::NoEffect {
+                name: defender.name.clone(),
+            });
+            return;
+        }
+
+        let defender_index = self.side(side.other()).active;
+        let defender = self.side_mut(side.other()).active_mut();
+        defender.take_damage(result.amount);
+        log.push(BattleLog::Damaged {
+            side: side.other(),
+            amount: result.amount,
+        });
+        if result.critical {
+            log.push(BattleLog::CriticalHit);
+        }
+        if result.effectiveness > 1.0 {
+            log.push(BattleLog::SuperEffective);
+        } else if result.effectiveness < 1.0 {
+            log.push(BattleLog::NotVeryEffective);
+        }
+
+        if defender.is_fainted() {
+            log.push(BattleLog::Fainted {
+                side: side.other(),
+                index: defender_index,
+                name: defender.name.clone(),
+            });
+            if self.side(side.other()).all_fainted() {
+                let outcome = match side {
+                    Side::Player => BattleOutcome::Won,
+                    Side::Opponent => BattleOutcome::Lost,
+                };
+                self.finish(outcome, log);
+            }
+        }
+    }
+
+    /// Rolls a capture of the opponent's active daemon, returning true if it was caught.
+    fn throw_orb(
+        &mut self,
+        bonus: f32,
+        data: &SpeciesRegistry,
+        rng: &mut impl Rng,
+        log: &mut Vec<BattleLog>,
+    ) -> bool {
+        let index = self.opponent.active;
+        let target = self.opponent.active();
+        let catch_rate = data.species.get(&target.species)
+            .map_or(0, |species| species.catch_rate);
+        let result = attempt_capture(&CaptureInput {
+            catch_rate,
+            max_hp: target.stats.hp,
+            hp: target.hp,
+            status: target.status,
+            orb_bonus: bonus,
+        }, rng);
+
+        log.push(BattleLog::OrbThrown);
+        log.extend((0..result.shakes).map(|_| BattleLog::OrbShook));
+        let name = target.name.clone();
+        if result.caught {
+            log.push(BattleLog::Captured { index, name });
+            self.finish(BattleOutcome::Captured, log);
+        } else {
+            log.push(BattleLog::BrokeFree { name });
+        }
+        result.caught
+    }
+
+    fn try_flee(&mut self, rng: &mut impl Rng) -> bool {
+        self.flee_attempts += 1;
+        let player_speed = self.player.active().stats.speed as u32;
+        let opponent_speed = (self.opponent.active().stats.speed as u32 / 4) % 256;
+        if opponent_speed == 0 {
+            return true;
+        }
+        let odds = player_speed * 32 / opponent_speed + 30 * self.flee_attempts;
+        odds > 255 || rng.gen_range(0..256) < odds
+    }
+
+    fn finish(&mut self, outcome: BattleOutcome, log: &mut Vec<BattleLog>) {
+        self.outcome = Some(outcome);
+        log.push(BattleLog::Ended(outcome));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::species::MoveList;
+
+    fn registry() -> SpeciesRegistry {
+        let moves: MoveList = ron::from_str(include_str!("../../assets/daemons/moves.moves.ron")).unwrap();
+        let types = ron::from_str(include_str!("../../assets/daemons/types.types.ron")).unwrap();
+        SpeciesRegistry::build([], moves, types).0
+    }
+
+    fn combatant(name: &str, speed: u16, hp: u16, moves: &[&str]) -> Combatant {
+        Combatant {
+            species: "pebblit".to_string(),
+            name: name.to_string(),
+            level: 10,
+            types: vec!["normal".to_string()],
+            stats: Stats { hp: 40, attack: 20, defense: 20, sp_attack: 20, sp_defense: 20, speed },
+            hp,
+            moves: moves.iter().map(|id| MoveSlot { id: id.to_string(), pp: 5 }).collect(),
+            status: StatusCondition::Healthy,
+        }
+    }
+
+    fn movers(log: &[BattleLog]) -> Vec<Side> {
+        log.iter()
+            .filter_map(|entry| match entry {
+                BattleLog::UsedMove { side, .. } => Some(*side),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn faster_daemons_and_higher_priority_go_first() {
+        let data = registry();
+        let mut rng = StdRng::seed_from_u64(7);
+        let fight = |player_speed, player_move, opponent_speed, rng: &mut StdRng| {
+            let mut battle = Battle::new(
+                BattleKind::Wild,
+                vec![combatant("Ally", player_speed, 40, &[player_move])],
+                vec![combatant("Foe", opponent_speed, 40, &["scratch"])],
+            );
+            movers(&battle.resolve_turn(BattleAction::Fight(0), BattleAction::Fight(0), &data, rng))
+        };
+        assert_eq!(fight(50, "scratch", 10, &mut rng), [Side::Player, Side::Opponent]);
+        assert_eq!(fight(10, "scratch", 50, &mut rng), [Side::Opponent, Side::Player]);
+        assert_eq!(fight(10, "quick_strike", 50, &mut rng), [Side::Player, Side::Opponent]);
+    }
+
+    #[test]
+    fn fainted_daemons_are_replaced_until_a_side_runs_out() {
+        let data = registry();
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut battle = Battle::new(
+            BattleKind::Trainer,
+            vec![combatant("Ally", 50, 40, &["scratch"])],
+            vec![combatant("Foe", 10, 1, &["scratch"]), combatant("Other", 10, 1, &["scratch"])],
+        );
+
+        let log = battle.resolve_turn(BattleAction::Fight(0), BattleAction::Fight(0), &data, &mut rng);
+        assert!(log.contains(&BattleLog::Fainted { side: Side::Opponent, index: 0, name: "Foe".to_string() }));
+        assert_eq!(log.last(), Some(&BattleLog::SentOut { side: Side::Opponent, index: 1, name: "Other".to_string() }));
+        assert_eq!(movers(&log), [Side::Player]);
+        assert_eq!(battle.outcome, None);
+
+        let log = battle.resolve_turn(BattleAction::Fight(0), BattleAction::Fight(0), &data, &mut rng);
+        assert_eq!(log.last(), Some(&BattleLog::Ended(BattleOutcome::Won)));
+        assert_eq!(battle.outcome, Some(BattleOutcome::Won));
+        assert!(battle.resolve_turn(BattleAction::Fight(0), BattleAction::Fight(0), &data, &mut rng).is_empty());
+    }
+
+    #[test]
+    fn switching_goes_before_moves_and_losing_ends_the_battle() {
+        let data = registry();
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut battle = Battle::new(
+            BattleKind::Wild,
+            vec![combatant("Ally", 10, 40, &["scratch"]), combatant("Backup", 10, 1, &["scratch"])],
+            vec![combatant("Foe", 50, 40, &["scratch"])],
+        );
+
+        let log = battle.resolve_turn(BattleAction::Switch(1), BattleAction::Fight(0), &data, &mut rng);
+        assert_eq!(log[0], BattleLog::SentOut { side: Side::Player, index: 1, name: "Backup".to_string() });
+        // Backup faints to the foe's move and Ally comes back out.
+        assert!(log.contains(&BattleLog::Fainted { side: Side::Player, index: 1, name: "Backup".to_string() }));
+        assert_eq!(log.last(), Some(&BattleLog::SentOut { side: Side::Player, index: 0, name: "Ally".to_string() }));
+
+        battle.player.daemons[0].hp = 1;
+        let log = battle.resolve_turn(BattleAction::Fight(0), BattleAction::Fight(0), &data, &mut rng);
+        assert_eq!(log.last(), Some(&BattleLog::Ended(BattleOutcome::Lost)));
+        assert_eq!(movers(&log), [Side::Opponent]);
+    }
+
+    #[test]
+    fn fleeing() {
+        let data = registry();
+        let mut rng = StdRng::seed_from_u64(7);
+        let sides = || (vec![combatant("Ally", 50, 40, &["scratch"])], vec![combatant("Foe", 3, 40, &["scratch"])]);
+
+        let (player, opponent) = sides();
+        let mut battle = Battle::new(BattleKind::Trainer, player, opponent);
+        let log = battle.resolve_turn(BattleAction::Run, BattleAction::Fight(0), &data, &mut rng);
+        assert_eq!(log, [BattleLog::CannotFlee]);
+        assert_eq!(battle.outcome, None);
+
+        let (player, opponent) = sides();
+        let mut battle = Battle::new(BattleKind::Wild, player, opponent);
+        let log = battle.resolve_turn(BattleAction::Run, BattleAction::Fight(0), &data, &mut rng);
+        assert_eq!(log, [BattleLog::Ended(BattleOutcome::Fled)]);
+    }
+
+    #[test]
+    fn unknown_moves_keep_their_pp() {
+        let data = registry();
+        let mut battle = Battle::new(
+            BattleKind::Wild,
+            vec![combatant("Ally", 50, 40, &["no_such_move"])],
+            vec![combatant("Foe", 10, 40, &["scratch"])],
+        );
+        let log = battle.resolve_turn(BattleAction::Fight(0), BattleAction::Fight(0), &data, &mut StdRng::seed_from_u64(7));
+        assert_eq!(movers(&log), [Side::Opponent]);
+        assert_eq!(battle.player.active().moves[0].pp, 5);
+    }
+}
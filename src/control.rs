@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::collections::HashSet;
 
 use bevy::prelude::*;
 use bevy::prelude::{
@@ -6,6 +7,12 @@ use bevy::prelude::{
     KeyCode, 
     Res,
 };
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::persist;
+use crate::persist::Persistence;
+use crate::replay::is_replaying;
 
 pub struct ControlPlugin;
 
@@ -20,34 +27,100 @@ impl Plugin for ControlPlugin {
         )
         .add_systems(
             Update,
-            map_inputs_to_control_events,
+            map_inputs_to_control_events.run_if(not(is_replaying)),
         );
     }
 }
 
-#[derive(Hash, PartialEq, Eq)]
+/// How far an analog stick has to be tilted before it counts as pressed.
+pub const STICK_DEADZONE: f32 = 0.5;
+
+/// Stick axes that are read as digital inputs.
+const STICK_AXES: [GamepadAxis; 4] = [
+    GamepadAxis::LeftStickX,
+    GamepadAxis::LeftStickY,
+    GamepadAxis::RightStickX,
+    GamepadAxis::RightStickY,
+];
+
+#[derive(Hash, PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
+pub enum AxisDirection {
+    Positive,
+    Negative,
+}
+
+impl AxisDirection {
+    /// The direction `value` is tilted in, if it is outside the deadzone.
+    pub fn of(value: f32) -> Option<Self> {
+        if value >= STICK_DEADZONE {
+            Some(AxisDirection::Positive)
+        } else if value <= -STICK_DEADZONE {
+            Some(AxisDirection::Negative)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Hash, PartialEq, Eq, Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum Input {
     Keyboard(KeyCode),
+    Gamepad(GamepadButton),
+    /// An analog stick tilted past [`STICK_DEADZONE`], treated like a button.
+    Stick(GamepadAxis, AxisDirection),
 }
 
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum GameControl {
     Up,
     Down,
     Left,
     Right,
     Interact,
+    /// Backs out of menus and dialog.
+    Cancel,
+    /// Opens the pause menu.
+    Start,
+    /// Held to walk faster.
+    Run,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+impl GameControl {
+    /// Every control, in the order the options screen lists them.
+    pub const ALL: [GameControl; 8] = [
+        GameControl::Up,
+        GameControl::Down,
+        GameControl::Left,
+        GameControl::Right,
+        GameControl::Interact,
+        GameControl::Cancel,
+        GameControl::Start,
+        GameControl::Run,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GameControl::Up => "Up",
+            GameControl::Down => "Down",
+            GameControl::Left => "Left",
+            GameControl::Right => "Right",
+            GameControl::Interact => "Interact",
+            GameControl::Cancel => "Cancel",
+            GameControl::Start => "Start",
+            GameControl::Run => "Run",
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ControlStatus {
     Pressed,
     JustPressed,
-    // JustReleased,
+    JustReleased,
 }
 
-#[derive(Event, Debug, PartialEq)]
+#[derive(Event, Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub struct GameControlEvent {
     pub control: GameControl,
     pub status: ControlStatus,
@@ -71,52 +144,347 @@ impl GameControlEvent {
     }
 }
 
-#[derive(Default, Resource, Deref)]
+/// Name of the persisted bindings, see [`persist`].
+const INPUT_MAP_NAME: &str = "controls";
+
+/// Serialized as a list of [`Binding`]s, as JSON keys can only be strings.
+#[derive(Default, Resource, Deref, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "Vec<Binding>", into = "Vec<Binding>")]
 // #[deref(forward)]
 pub struct InputMap(HashMap<Input, GameControl>);
 
+/// The input is already bound to a different control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BindingConflict {
+    pub input: Input,
+    pub bound_to: GameControl,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Binding {
+    input: Input,
+    control: GameControl,
+}
+
+impl From<Vec<Binding>> for InputMap {
+    fn from(bindings: Vec<Binding>) -> Self {
+        InputMap(bindings.into_iter()
+            .map(|binding| (binding.input, binding.control))
+            .collect())
+    }
+}
+
+impl From<InputMap> for Vec<Binding> {
+    fn from(input_map: InputMap) -> Self {
+        input_map.0.into_iter()
+            .map(|(input, control)| Binding { input, control })
+            .collect()
+    }
+}
+
+impl InputMap {
+    pub fn defaults() -> Self {
+        InputMap(HashMap::from([
+            (Input::Keyboard(KeyCode::KeyW), GameControl::Up),
+            (Input::Keyboard(KeyCode::KeyS), GameControl::Down),
+            (Input::Keyboard(KeyCode::KeyA), GameControl::Left),
+            (Input::Keyboard(KeyCode::KeyD), GameControl::Right),
+            (Input::Keyboard(KeyCode::ArrowUp), GameControl::Up),
+            (Input::Keyboard(KeyCode::ArrowDown), GameControl::Down),
+            (Input::Keyboard(KeyCode::ArrowLeft), GameControl::Left),
+            (Input::Keyboard(KeyCode::ArrowRight), GameControl::Right),
+            (Input::Keyboard(KeyCode::Space), GameControl::Interact),
+            (Input::Keyboard(KeyCode::Enter), GameControl::Interact),
+            (Input::Keyboard(KeyCode::Escape), GameControl::Cancel),
+            (Input::Keyboard(KeyCode::Backspace), GameControl::Cancel),
+            (Input::Keyboard(KeyCode::Tab), GameControl::Start),
+            (Input::Keyboard(KeyCode::KeyM), GameControl::Start),
+            (Input::Keyboard(KeyCode::ShiftLeft), GameControl::Run),
+            (Input::Keyboard(KeyCode::ShiftRight), GameControl::Run),
+            (Input::Gamepad(GamepadButton::DPadUp), GameControl::Up),
+            (Input::Gamepad(GamepadButton::DPadDown), GameControl::Down),
+            (Input::Gamepad(GamepadButton::DPadLeft), GameControl::Left),
+            (Input::Gamepad(GamepadButton::DPadRight), GameControl::Right),
+            (Input::Gamepad(GamepadButton::South), GameControl::Interact),
+            (Input::Gamepad(GamepadButton::East), GameControl::Cancel),
+            (Input::Gamepad(GamepadButton::Start), GameControl::Start),
+            (Input::Gamepad(GamepadButton::West), GameControl::Run),
+            (Input::Stick(GamepadAxis::LeftStickY, AxisDirection::Positive), GameControl::Up),
+            (Input::Stick(GamepadAxis::LeftStickY, AxisDirection::Negative), GameControl::Down),
+            (Input::Stick(GamepadAxis::LeftStickX, AxisDirection::Negative), GameControl::Left),
+            (Input::Stick(GamepadAxis::LeftStickX, AxisDirection::Positive), GameControl::Right),
+        ]))
+    }
+
+    /// Keys bound to `control`, in a stable order.
+    pub fn keys_for(&self, control: GameControl) -> Vec<KeyCode> {
+        let mut keys: Vec<KeyCode> = self.iter()
+            .filter(|(_, bound)| **bound == control)
+            .filter_map(|(input, _)| match input {
+                Input::Keyboard(key) => Some(*key),
+                _ => None,
+            })
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    /// Binds `key` to `control` in place of `replacing`, or in addition to the
+    /// existing keys if `replacing` is `None`.
+    pub fn rebind(
+        &mut self,
+        control: GameControl,
+        replacing: Option<KeyCode>,
+        key: KeyCode,
+    ) -> Result<(), BindingConflict> {
+        let input = Input::Keyboard(key);
+        match self.get(&input) {
+            Some(bound_to) if *bound_to != control => {
+                return Err(BindingConflict { input, bound_to: *bound_to });
+            }
+            _ => {}
+        }
+        if let Some(old) = replacing {
+            self.0.remove(&Input::Keyboard(old));
+        }
+        self.0.insert(input, control);
+        Ok(())
+    }
+
+    /// Adds the default bindings of controls that have none, e.g. controls
+    /// added after the bindings were saved.
+    fn fill_unbound(&mut self) {
+        let bound: HashSet<GameControl> = self.values().copied().collect();
+        for (input, control) in InputMap::defaults().0 {
+            if !bound.contains(&control) {
+                self.0.entry(input).or_insert(control);
+            }
+        }
+    }
+
+    fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json)
+    }
+
+    /// Writes the bindings so they are used on the next start.
+    pub fn save(&self, persistence: &mut Persistence) {
+        let result = self.to_json()
+            .map_err(|error| error.to_string())
+            .and_then(|json| persistence.write(INPUT_MAP_NAME, &json).map_err(|error| error.to_string()));
+        if let Err(error) = result {
+            error!("Could not save controls: {}", error);
+        }
+    }
+}
+
+/// Short name of a key for menus, e.g. `W` rather than `KeyW`.
+pub fn key_label(key: KeyCode) -> String {
+    let name = format!("{:?}", key);
+    name.strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+/// Loads the saved bindings, falling back to the defaults.
 pub fn init_input_map(
     mut input_map: ResMut<InputMap>,
 ) {
-    *input_map = InputMap(HashMap::from([
-        (Input::Keyboard(KeyCode::KeyW), GameControl::Up),
-        (Input::Keyboard(KeyCode::KeyS), GameControl::Down),
-        (Input::Keyboard(KeyCode::KeyA), GameControl::Left),
-        (Input::Keyboard(KeyCode::KeyD), GameControl::Right),
-        (Input::Keyboard(KeyCode::ArrowUp), GameControl::Up),
-        (Input::Keyboard(KeyCode::ArrowDown), GameControl::Down),
-        (Input::Keyboard(KeyCode::ArrowLeft), GameControl::Left),
-        (Input::Keyboard(KeyCode::ArrowRight), GameControl::Right),
-        (Input::Keyboard(KeyCode::Space), GameControl::Interact),
-        (Input::Keyboard(KeyCode::Enter), GameControl::Interact),
-    ]));
+    let saved = match persist::read(INPUT_MAP_NAME) {
+        Ok(json) => json,
+        Err(error) => {
+            warn!("Could not read controls: {}", error);
+            None
+        }
+    };
+    *input_map = match saved.map(|json| InputMap::from_json(&json)) {
+        Some(Ok(mut saved)) => {
+            saved.fill_unbound();
+            saved
+        }
+        Some(Err(error)) => {
+            warn!("Ignoring saved controls: {}", error);
+            InputMap::defaults()
+        }
+        None => InputMap::defaults(),
+    };
 }
 
+/// Sends a [`GameControlEvent`] for every mapped input that is held or changed,
+/// from the keyboard and any connected gamepad alike.
+///
+/// Sticks have no pressed state of their own, so the directions held last frame
+/// are remembered to tell when one is just pressed or released.
 pub fn map_inputs_to_control_events(
     mut control: EventWriter<GameControlEvent>,
     input_map: Res<InputMap>,
     keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Query<&Gamepad>,
+    mut held_sticks: Local<HashSet<Input>>,
 ) {
-    for key in keys.get_pressed() {
-        match input_map.get(&Input::Keyboard(*key)) {
-            Some(c) => {
-                control.send(GameControlEvent{
-                   control: *c,
-                   status: ControlStatus::Pressed,
-                });
-            },
-            None => {},
+    let mut pressed: Vec<Input> = keys.get_pressed()
+        .map(|key| Input::Keyboard(*key))
+        .collect();
+    let mut just_pressed: Vec<Input> = keys.get_just_pressed()
+        .map(|key| Input::Keyboard(*key))
+        .collect();
+    let mut just_released: Vec<Input> = keys.get_just_released()
+        .map(|key| Input::Keyboard(*key))
+        .collect();
+
+    let mut sticks = HashSet::new();
+    for gamepad in &gamepads {
+        pressed.extend(gamepad.get_pressed().map(|button| Input::Gamepad(*button)));
+        just_pressed.extend(gamepad.get_just_pressed().map(|button| Input::Gamepad(*button)));
+        just_released.extend(gamepad.get_just_released().map(|button| Input::Gamepad(*button)));
+        for axis in STICK_AXES {
+            let value = gamepad.get(axis).unwrap_or(0.);
+            if let Some(direction) = AxisDirection::of(value) {
+                sticks.insert(Input::Stick(axis, direction));
+            }
         }
     }
-    for key in keys.get_just_pressed() {
-        match input_map.get(&Input::Keyboard(*key)) {
-            Some(c) => {
+    pressed.extend(sticks.iter().copied());
+    just_pressed.extend(sticks.difference(&held_sticks).copied());
+    just_released.extend(held_sticks.difference(&sticks).copied());
+    *held_sticks = sticks;
+
+    for (inputs, status) in [
+        (pressed, ControlStatus::Pressed),
+        (just_pressed, ControlStatus::JustPressed),
+        (just_released, ControlStatus::JustReleased),
+    ] {
+        for input in inputs {
+            if let Some(c) = input_map.get(&input) {
                 control.send(GameControlEvent{
                    control: *c,
-                   status: ControlStatus::JustPressed,
+                   status,
                 });
-            },
-            None => {},
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::input::keyboard::Key;
+    use bevy::input::keyboard::KeyboardInput;
+    use bevy::input::keyboard::NativeKey;
+    use bevy::input::ButtonState;
+    use crate::testing::TestApp;
+
+    /// Sends `key` the way a window would and returns the controls of the tick.
+    fn key_event(app: &mut TestApp, key: KeyCode, state: ButtonState) -> Vec<GameControlEvent> {
+        app.app.world_mut().send_event(KeyboardInput {
+            key_code: key,
+            logical_key: Key::Unidentified(NativeKey::Unidentified),
+            state,
+            repeat: false,
+            window: Entity::PLACEHOLDER,
+        });
+        app.tick(1);
+        app.app.world_mut().resource_mut::<Events<GameControlEvent>>().drain().collect()
+    }
+
+    #[test]
+    fn sticks_count_from_the_deadzone() {
+        assert_eq!(AxisDirection::of(STICK_DEADZONE), Some(AxisDirection::Positive));
+        assert_eq!(AxisDirection::of(-STICK_DEADZONE), Some(AxisDirection::Negative));
+        assert_eq!(AxisDirection::of(0.9), Some(AxisDirection::Positive));
+        assert_eq!(AxisDirection::of(-0.9), Some(AxisDirection::Negative));
+        assert_eq!(AxisDirection::of(STICK_DEADZONE - 0.01), None);
+        assert_eq!(AxisDirection::of(-STICK_DEADZONE + 0.01), None);
+        assert_eq!(AxisDirection::of(0.), None);
+    }
+
+    #[test]
+    fn tilting_a_stick_presses_and_releases() {
+        let mut app = TestApp::new();
+        app.app.insert_resource(InputMap::defaults());
+        let gamepad = app.app.world_mut().spawn(Gamepad::default()).id();
+        let mut tilt = |value: f32| {
+            app.app.world_mut().get_mut::<Gamepad>(gamepad).unwrap()
+                .analog_mut()
+                .set(GamepadAxis::LeftStickX, value);
+            app.tick(1);
+            app.app.world_mut().resource_mut::<Events<GameControlEvent>>().drain()
+                .map(|event| (event.control, event.status))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(tilt(0.8), [
+            (GameControl::Right, ControlStatus::Pressed),
+            (GameControl::Right, ControlStatus::JustPressed),
+        ]);
+        assert_eq!(tilt(0.8), [(GameControl::Right, ControlStatus::Pressed)]);
+        assert_eq!(tilt(0.3), [(GameControl::Right, ControlStatus::JustReleased)]);
+        assert_eq!(tilt(-STICK_DEADZONE), [
+            (GameControl::Left, ControlStatus::Pressed),
+            (GameControl::Left, ControlStatus::JustPressed),
+        ]);
+        assert_eq!(tilt(0.), [(GameControl::Left, ControlStatus::JustReleased)]);
+        assert_eq!(tilt(0.), []);
+    }
+
+    #[test]
+    fn releasing_a_key_is_sent_once() {
+        let mut app = TestApp::new();
+        let pressed = key_event(&mut app, KeyCode::KeyW, ButtonState::Pressed);
+        assert!(pressed.iter().any(|event| event.control == GameControl::Up && event.just_pressed()));
+
+        let released = key_event(&mut app, KeyCode::KeyW, ButtonState::Released);
+        assert_eq!(released, vec![GameControlEvent { control: GameControl::Up, status: ControlStatus::JustReleased }]);
+
+        app.tick(1);
+        let after: Vec<GameControlEvent> =
+            app.app.world_mut().resource_mut::<Events<GameControlEvent>>().drain().collect();
+        assert!(after.is_empty());
+    }
+
+    #[test]
+    fn rebinding_replaces_the_slot() {
+        let mut input_map = InputMap::defaults();
+        assert_eq!(input_map.keys_for(GameControl::Up), vec![KeyCode::KeyW, KeyCode::ArrowUp]);
+
+        input_map.rebind(GameControl::Up, Some(KeyCode::KeyW), KeyCode::KeyI).unwrap();
+        assert_eq!(input_map.keys_for(GameControl::Up), vec![KeyCode::KeyI, KeyCode::ArrowUp]);
+        assert_eq!(input_map.get(&Input::Keyboard(KeyCode::KeyW)), None);
+    }
+
+    #[test]
+    fn rebinding_detects_conflicts() {
+        let mut input_map = InputMap::defaults();
+        let conflict = input_map.rebind(GameControl::Up, Some(KeyCode::KeyW), KeyCode::Space);
+        assert_eq!(conflict, Err(BindingConflict {
+            input: Input::Keyboard(KeyCode::Space),
+            bound_to: GameControl::Interact,
+        }));
+        assert_eq!(input_map, InputMap::defaults());
+    }
+
+    #[test]
+    fn new_controls_get_default_bindings() {
+        let mut input_map = InputMap(HashMap::from([
+            (Input::Keyboard(KeyCode::KeyI), GameControl::Up),
+            (Input::Keyboard(KeyCode::Tab), GameControl::Interact),
+        ]));
+        input_map.fill_unbound();
+        assert_eq!(input_map.keys_for(GameControl::Up), vec![KeyCode::KeyI]);
+        assert_eq!(input_map.keys_for(GameControl::Interact), vec![KeyCode::Tab]);
+        assert_eq!(input_map.keys_for(GameControl::Start), vec![KeyCode::KeyM]);
+        assert_eq!(input_map.keys_for(GameControl::Run), vec![KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    }
+
+    #[test]
+    fn bindings_round_trip() {
+        let mut input_map = InputMap::defaults();
+        input_map.rebind(GameControl::Interact, None, KeyCode::KeyE).unwrap();
+        let json = input_map.to_json().unwrap();
+        assert_eq!(InputMap::from_json(&json).unwrap(), input_map);
+    }
+}
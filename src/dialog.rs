@@ -3,17 +3,36 @@ use crate::GameState;
 use bevy::sprite::*;
 
 use crate::text_loading::Dialog;
+use crate::text_loading::GameText;
+use crate::text_loading::Span;
+use crate::text_loading::SpanEffect;
+use crate::text_layout::paginate;
+use crate::text_layout::PageLayout;
+use crate::text_layout::TextMeasure;
+use crate::text_variables::TextVariables;
 use crate::RES_WIDTH;
 use crate::PIXEL_PERFECT_STATIC_LAYERS;
 use crate::loading::TextureAssets;
 use crate::loading::FontAssets;
+use crate::loading::DialogAssets;
+use crate::speaker::SpeakerList;
 
 use crate::control::GameControlEvent;
 use crate::control::GameControl;
 use crate::state_stack::StateStack;
+use crate::graph::grid_transform::GridTransform;
+use crate::menu::MenuBox;
+use crate::menu::MenuCursor;
+use crate::menu::MenuElement;
+use crate::menu::TriggerOnMenuInteract;
+use crate::mob::TriggerEvent;
 
+use bevy::text::ComputedTextBlock;
 use bevy::text::TextBounds;
+use bevy::text::TextLayoutInfo;
 use bevy::text::LineBreak;
+use std::f32::consts::FRAC_PI_2;
+use std::time::Duration;
 
 pub struct DialogPlugin;
 
@@ -29,10 +48,17 @@ impl Plugin for DialogPlugin {
             // Update systems for dialog state
             .add_systems(Update, (
                 dialog_control,
+                dialog_choice_control,
                 change_page,
                 update_current_page_text, // Tick timer and update index
                 stream_text,              // Update displayed text if changed
+                show_choices,
+                show_more_indicator,
+                show_speaker,
             ).chain().run_if(in_state(GameState::Dialog)))
+            .add_systems(PostUpdate, (
+                animate_span_effects.after(bevy::text::update_text2d_layout),
+            ).run_if(in_state(GameState::Dialog)))
             .add_systems(OnExit(GameState::Dialog), (
                 exit_dialog,
             ))
@@ -40,7 +66,8 @@ impl Plugin for DialogPlugin {
             .init_resource::<CurrentPageIndex>()
             .init_resource::<CurrentPageText>()
             .init_resource::<TextRevealTimer>()
-            .add_event::<PageEvent>();
+            .add_event::<PageEvent>()
+            .add_event::<DialogOutcomeEvent>();
     }
 }
 
@@ -53,38 +80,139 @@ pub struct CurrentPageIndex(usize);
 #[derive(Event, Default, Deref, DerefMut)]
 pub struct PageEvent(usize);
 
+/// Sent when the player picks a [`crate::text_loading::Choice`] with an
+/// outcome, for other systems to act on.
+#[derive(Event, Debug, Clone, PartialEq)]
+pub struct DialogOutcomeEvent {
+    pub outcome: String,
+}
+
 #[derive(Resource, Default, Debug)]
 pub struct CurrentPageText {
-    pub full_text: String,
+    pub spans: Vec<Span>,
+    /// Characters revealed so far, counted across all spans.
     pub current_index: usize,
 }
 
-#[derive(Resource, Deref, DerefMut, Reflect, Debug)]
-pub struct TextRevealTimer(Timer);
+impl CurrentPageText {
+    pub fn char_count(&self) -> usize {
+        self.spans.iter().map(|span| span.text.chars().count()).sum()
+    }
+
+    pub fn is_revealed(&self) -> bool {
+        self.current_index >= self.char_count()
+    }
+
+    pub fn reveal_all(&mut self) {
+        self.current_index = self.char_count();
+    }
 
-impl Default for TextRevealTimer {
-    fn default() -> Self {
-        TextRevealTimer(Timer::from_seconds(
-            0.05, // adjust speed as desired
-            TimerMode::Repeating
-        ))
+    /// How long to wait before revealing the next character, or `None` once
+    /// everything is revealed. The first character of a span also waits out
+    /// the span's `pausebefore`.
+    pub fn next_delay(&self) -> Option<Duration> {
+        let mut index = self.current_index;
+        for span in &self.spans {
+            let len = span.text.chars().count();
+            if index < len {
+                let delay = CHARACTER_DELAY.div_f64(span.speed.max(0.1) as f64);
+                return Some(if index == 0 {
+                    delay + Duration::from_millis(span.pausebefore)
+                } else {
+                    delay
+                });
+            }
+            index -= len;
+        }
+        None
+    }
+
+    /// The revealed part of each span.
+    fn revealed(&self) -> impl Iterator<Item = &str> {
+        let mut remaining = self.current_index;
+        self.spans.iter().map(move |span| {
+            let end = span.text.char_indices()
+                .nth(remaining)
+                .map_or(span.text.len(), |(end, _)| end);
+            remaining = remaining.saturating_sub(span.text.chars().count());
+            &span.text[..end]
+        })
     }
 }
 
+/// Time per character at a span speed of 1.
+const CHARACTER_DELAY: Duration = Duration::from_millis(50);
+
+const TEXT_COLOR: Color = Color::srgb(47. / 255., 76. / 255., 64. / 255.);
+const FONT_SIZE: f32 = 10.;
+const BOX_SIZE: Vec2 = Vec2::new(RES_WIDTH as f32 - 16., 48.);
+/// Left edge of the text in the dialog box.
+const TEXT_X: f32 = 8.;
+/// Left edge of the choice menu in the dialog box.
+const CHOICE_MENU_X: f32 = RES_WIDTH as f32 - 56.;
+const NAME_PLATE_SIZE: Vec2 = Vec2::new(64., 16.);
+const PORTRAIT_FRAME_SIZE: Vec2 = Vec2::new(24., 24.);
+
+/// Time since the last character was revealed.
+#[derive(Resource, Default, Deref, DerefMut, Reflect, Debug)]
+pub struct TextRevealTimer(Duration);
+
 #[derive(Component)]
 struct DialogBox;
 
 #[derive(Component)]
 struct DialogText;
 
+/// Index of the page span a text span of [`DialogText`] shows.
+#[derive(Component)]
+struct DialogSpan(usize);
+
+/// Arrow shown once a page is revealed if its text carries on in the next.
+#[derive(Component)]
+struct DialogMoreIndicator;
+
+/// Parts of the dialog box showing who is speaking, above its top edge.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum SpeakerDisplay {
+    NamePlate,
+    PortraitFrame,
+}
+
+#[derive(Component)]
+struct DialogSpeakerName;
+
+#[derive(Component)]
+struct DialogPortrait;
+
+/// The choice menu and its cursor, spawned while a choice page is shown.
+#[derive(Component)]
+struct DialogChoiceMenu;
+
+/// Index of a choice on the current page.
+#[derive(Component)]
+struct DialogChoice(usize);
+
 fn init_dialog(
     mut commands: Commands,
     textures: Res<TextureAssets>,
     fonts: Res<FontAssets>,
 ) {
-    let box_size = Vec2::new(RES_WIDTH as f32 - 16., 48.);
+    let box_size = BOX_SIZE;
     let text_box_size = Vec2::new(box_size.x - 4., box_size.y - 4.);
 
+    let sliced_box = |size: Vec2| Sprite {
+        image: textures.dialog_box.clone(),
+        custom_size: Some(size),
+        anchor: Anchor::BottomLeft,
+        image_mode: SpriteImageMode::Sliced(TextureSlicer {
+            border: BorderRect::square(8.),
+            center_scale_mode: SliceScaleMode::Stretch,
+            sides_scale_mode: SliceScaleMode::Stretch,
+            max_corner_scale: 1.0,
+        }),
+        ..default()
+    };
+
     commands.spawn((
         Name::new("Dialog Box".to_string()),
         Transform::from_xyz(
@@ -92,18 +220,7 @@ fn init_dialog(
             8.,
             0.,
         ),
-        Sprite {
-            image: textures.dialog_box.clone(),
-            custom_size: Some(box_size),
-            anchor: Anchor::BottomLeft,
-            image_mode: SpriteImageMode::Sliced(TextureSlicer {
-                border: BorderRect::square(8.),
-                center_scale_mode: SliceScaleMode::Stretch,
-                sides_scale_mode: SliceScaleMode::Stretch,
-                max_corner_scale: 1.0,
-            }),
-            ..default()
-        },
+        sliced_box(box_size),
         Visibility::Hidden,
         PIXEL_PERFECT_STATIC_LAYERS,
         DialogBox,
@@ -111,10 +228,10 @@ fn init_dialog(
         builder.spawn((
             Name::new("Dialog Text".to_string()),
             Text2d::new("".to_string()),
-            TextColor(Color::srgb_u8(47, 76, 64)),
+            TextColor(TEXT_COLOR),
             TextFont {
                 font: fonts.font.clone(),
-                font_size: 10.,
+                font_size: FONT_SIZE,
                 ..Default::default()
             },
             TextLayout {
@@ -127,74 +244,186 @@ fn init_dialog(
                 height: Some(text_box_size.y), 
             },
             Transform::from_xyz(
-                8.,
+                TEXT_X,
                 box_size.y - 2.,
                 10.,
             ),
             PIXEL_PERFECT_STATIC_LAYERS,
             DialogText,
         ));
+        builder.spawn((
+            Name::new("Dialog More Indicator".to_string()),
+            Sprite {
+                image: textures.menu_pointer.clone(),
+                ..default()
+            },
+            Transform::from_xyz(box_size.x - 10., 8., 10.)
+                .with_rotation(Quat::from_rotation_z(-FRAC_PI_2)),
+            Visibility::Hidden,
+            PIXEL_PERFECT_STATIC_LAYERS,
+            DialogMoreIndicator,
+        ));
+        builder.spawn((
+            Name::new("Dialog Name Plate".to_string()),
+            sliced_box(NAME_PLATE_SIZE),
+            Transform::from_xyz(0., box_size.y - 2., 5.),
+            Visibility::Hidden,
+            PIXEL_PERFECT_STATIC_LAYERS,
+            SpeakerDisplay::NamePlate,
+        )).with_child((
+            Text2d::new("".to_string()),
+            TextColor(TEXT_COLOR),
+            TextFont {
+                font: fonts.font.clone(),
+                font_size: FONT_SIZE,
+                ..Default::default()
+            },
+            Anchor::BottomLeft,
+            Transform::from_xyz(6., 3., 1.),
+            PIXEL_PERFECT_STATIC_LAYERS,
+            DialogSpeakerName,
+        ));
+        builder.spawn((
+            Name::new("Dialog Portrait".to_string()),
+            sliced_box(PORTRAIT_FRAME_SIZE),
+            Transform::from_xyz(box_size.x - PORTRAIT_FRAME_SIZE.x, box_size.y - 2., 5.),
+            Visibility::Hidden,
+            PIXEL_PERFECT_STATIC_LAYERS,
+            SpeakerDisplay::PortraitFrame,
+        )).with_child((
+            Sprite::default(),
+            Transform::from_translation((PORTRAIT_FRAME_SIZE / 2.).extend(1.)),
+            PIXEL_PERFECT_STATIC_LAYERS,
+            DialogPortrait,
+        ));
     });
 }
 
+/// Wraps the dialog to the box before showing its first page, measuring
+/// placeholders by what they would be filled in with now.
 fn enter_dialog(
     mut events: EventWriter<PageEvent>,
     mut dialog_box_query: Query<&mut Visibility, With<DialogBox>>,
+    mut current_dialog: ResMut<CurrentDialog>,
+    fonts: Res<FontAssets>,
+    font_assets: Res<Assets<Font>>,
+    variables: Res<TextVariables>,
+    game_text: Res<GameText>,
 ) {
     for mut visibility in &mut dialog_box_query {
         *visibility = Visibility::Inherited;
     }
+    if let Some(dialog) = current_dialog.0.as_mut() {
+        let measure = font_assets.get(&fonts.font)
+            .zip(font_assets.get(&fonts.bold_font))
+            .and_then(|(regular, bold)| TextMeasure::new(regular, bold, FONT_SIZE));
+        match measure {
+            Some(measure) => {
+                let layout = PageLayout {
+                    width: BOX_SIZE.x - 2. * TEXT_X,
+                    choice_width: CHOICE_MENU_X - 2. * TEXT_X,
+                    lines: ((BOX_SIZE.y - 4.) / measure.line_height()) as usize,
+                };
+                *dialog = paginate(dialog, layout, |span, text| {
+                    measure.width(span, &variables.resolve(text, game_text.plural_rule))
+                });
+            }
+            None => warn!("Dialog fonts could not be read, showing dialog unwrapped"),
+        }
+    }
     events.send(PageEvent(0));
 }
 
+/// Shows page `event` with one text span per page span, each styled on its
+/// own, and its placeholders filled in.
 fn change_page(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    choice_menu_query: Query<Entity, With<DialogChoiceMenu>>,
+    dialog_text_query: Query<Entity, With<DialogText>>,
     mut events: EventReader<PageEvent>,
     mut current_page_index: ResMut<CurrentPageIndex>,
     mut current_page_text: ResMut<CurrentPageText>,
+    mut timer: ResMut<TextRevealTimer>,
     current_dialog: Res<CurrentDialog>,
+    variables: Res<TextVariables>,
+    game_text: Res<GameText>,
 ) {
     for event in events.read() {
+        for entity in &choice_menu_query {
+            commands.entity(entity).despawn_recursive();
+        }
         *current_page_index = CurrentPageIndex(**event);
         if let Some(dialog) = (*current_dialog).as_ref() {
-            let full_text = dialog[**event].spans.iter()
-                .map(|x| x.text.clone())
-                .collect::<Vec<_>>()
-                .join("");
+            let spans: Vec<Span> = dialog[**event].spans.iter()
+                .map(|span| Span {
+                    text: variables.resolve(&span.text, game_text.plural_rule),
+                    ..span.clone()
+                })
+                .collect();
+            for dialog_text in &dialog_text_query {
+                commands.entity(dialog_text)
+                    .despawn_descendants()
+                    .with_children(|builder| {
+                        for (index, span) in spans.iter().enumerate() {
+                            builder.spawn((
+                                TextSpan::default(),
+                                TextFont {
+                                    font: if span.bold { fonts.bold_font.clone() } else { fonts.font.clone() },
+                                    font_size: FONT_SIZE,
+                                    ..Default::default()
+                                },
+                                TextColor(span.color
+                                    .map_or(TEXT_COLOR, |(r, g, b)| Color::srgb_u8(r, g, b))),
+                                DialogSpan(index),
+                            ));
+                        }
+                    });
+            }
 
-            current_page_text.full_text = full_text;
+            current_page_text.spans = spans;
             current_page_text.current_index = 0;
+            **timer = Duration::ZERO;
         }
     }
 }
 
 fn exit_dialog(
+    mut commands: Commands,
     mut dialog_box_query: Query<&mut Visibility, With<DialogBox>>,
+    choice_menu_query: Query<Entity, With<DialogChoiceMenu>>,
 ) {
+    for entity in &choice_menu_query {
+        commands.entity(entity).despawn_recursive();
+    }
     for mut visibility in &mut dialog_box_query {
         *visibility = Visibility::Hidden;
     }
 }
 
-/// System that updates the current page text based on the timer.
-/// This increments the current_page_text.current_index when the timer finishes.
+/// System that reveals characters as their delays pass, several in one frame
+/// for fast spans.
 fn update_current_page_text(
     time: Res<Time>,
     mut timer: ResMut<TextRevealTimer>,
     mut current_page_text: ResMut<CurrentPageText>,
 ) {
-    let full_len = current_page_text.full_text.len();
-    if current_page_text.current_index < full_len {
-        timer.tick(time.delta());
-        if timer.just_finished() {
-            // Reveal one more character
-            current_page_text.current_index += 1;
+    if current_page_text.is_revealed() {
+        return;
+    }
+    **timer += time.delta();
+    while let Some(delay) = current_page_text.next_delay() {
+        if **timer < delay {
+            break;
         }
+        **timer -= delay;
+        current_page_text.current_index += 1;
     }
 }
 
 /// System that updates the dialog text if the CurrentPageText resource changed.
 fn stream_text(
-    mut dialog_text_query: Query<&mut Text2d, With<DialogText>>,
+    mut span_query: Query<(&mut TextSpan, &DialogSpan)>,
     current_page_text: Res<CurrentPageText>,
 ) {
     // Only update if the resource has changed this frame.
@@ -202,9 +431,58 @@ fn stream_text(
         return;
     }
 
-    let displayed_text = &current_page_text.full_text[..current_page_text.current_index];
-    for mut text in &mut dialog_text_query {
-        *text = Text2d::new(displayed_text);
+    let revealed: Vec<&str> = current_page_text.revealed().collect();
+    for (mut text, DialogSpan(index)) in &mut span_query {
+        if let Some(displayed_text) = revealed.get(*index) {
+            if text.as_str() != *displayed_text {
+                **text = displayed_text.to_string();
+            }
+        }
+    }
+}
+
+/// Moves the letters of shaking and waving spans, starting from where the
+/// layout put them each frame. Runs after the layout so the offsets aren't
+/// overwritten, and doesn't flag its changes so it can tell when the layout
+/// has moved the letters itself.
+fn animate_span_effects(
+    time: Res<Time>,
+    current_page_text: Res<CurrentPageText>,
+    mut dialog_text_query: Query<(&mut TextLayoutInfo, &ComputedTextBlock), With<DialogText>>,
+    span_query: Query<&DialogSpan>,
+    mut laid_out: Local<Vec<Vec2>>,
+) {
+    for (mut layout, computed) in &mut dialog_text_query {
+        if layout.is_changed() {
+            *laid_out = layout.glyphs.iter().map(|glyph| glyph.position).collect();
+        }
+        let layout = layout.bypass_change_detection();
+        if laid_out.len() != layout.glyphs.len() {
+            continue;
+        }
+        for (index, glyph) in layout.glyphs.iter_mut().enumerate() {
+            let effect = computed.entities().get(glyph.span_index)
+                .and_then(|text_entity| span_query.get(text_entity.entity).ok())
+                .and_then(|DialogSpan(span)| current_page_text.spans.get(*span))
+                .map_or(SpanEffect::None, |span| span.effect);
+            glyph.position = laid_out[index] + effect_offset(effect, time.elapsed_secs(), index);
+        }
+    }
+}
+
+/// Whole pixel offset of letter `index` at `seconds`.
+fn effect_offset(effect: SpanEffect, seconds: f32, index: usize) -> Vec2 {
+    let index = index as f32;
+    match effect {
+        SpanEffect::None => Vec2::ZERO,
+        SpanEffect::Shake => {
+            let step = (seconds * 20.).floor();
+            Vec2::new(
+                (step * 1.7 + index * 2.3).sin().round(),
+                (step * 2.9 + index * 1.3).cos().round(),
+            )
+        }
+        SpanEffect::Wave => Vec2::new(0., ((seconds * 8. - index * 0.6).sin() * 1.5).round()),
     }
 }
 
@@ -224,23 +502,24 @@ pub fn dialog_control(
     {
         if !state.is_changed() {
             if let Some(dialog) = (*current_dialog).as_ref() {
-                let total_pages = dialog.len();
                 let current_index = current_page_index.0;
-                let full_text_len = current_page_text.full_text.len();
-                let current_len = current_page_text.current_index;
 
                 // If text not fully revealed yet, reveal it instantly
-                if current_len < full_text_len {
-                    current_page_text.current_index = full_text_len;
+                if !current_page_text.is_revealed() {
+                    current_page_text.reveal_all();
                     // current_page_text is now changed, stream_text will update on next frame
                     return;
                 }
 
+                // Choice pages are left through the choice menu instead
+                if !dialog[current_index].choices.is_empty() {
+                    return;
+                }
+
                 // If fully revealed, move to next page or close dialog
-                if current_index < total_pages - 1 {
-                    page_events.send(PageEvent(current_index + 1));
-                } else {
-                    next_state.set(state_stack.back());
+                match dialog.page_after(current_index) {
+                    Some(next_index) => { page_events.send(PageEvent(next_index)); }
+                    None => next_state.set(state_stack.back()),
                 }
             } else {
                 // If there's no dialog, just close.
@@ -249,3 +528,192 @@ pub fn dialog_control(
         }
     }
 }
+
+/// Shows the choices of the current page in the dialog box once its text is revealed.
+fn show_choices(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    fonts: Res<FontAssets>,
+    current_dialog: Res<CurrentDialog>,
+    current_page_index: Res<CurrentPageIndex>,
+    current_page_text: Res<CurrentPageText>,
+    dialog_box_query: Query<Entity, With<DialogBox>>,
+    choice_menu_query: Query<(), With<DialogChoiceMenu>>,
+) {
+    let Some(dialog) = (*current_dialog).as_ref() else { return };
+    let choices = &dialog[current_page_index.0].choices;
+    if choices.is_empty()
+        || !current_page_text.is_revealed()
+        || !choice_menu_query.is_empty()
+    {
+        return;
+    }
+    let Ok(dialog_box) = dialog_box_query.get_single() else { return };
+
+    let text_font = TextFont {
+        font: fonts.font.clone(),
+        font_size: FONT_SIZE,
+        ..Default::default()
+    };
+
+    let mut menu_box = Entity::PLACEHOLDER;
+    commands.entity(dialog_box).with_children(|builder| {
+        menu_box = builder.spawn((
+            Transform::from_xyz(CHOICE_MENU_X, 32., 10.),
+            Visibility::Inherited,
+            PIXEL_PERFECT_STATIC_LAYERS,
+            MenuBox::default(),
+            DialogChoiceMenu,
+        )).with_children(|builder| {
+            for (index, choice) in choices.iter().enumerate() {
+                builder.spawn((
+                    Text2d::new(choice.text.clone()),
+                    text_font.clone(),
+                    TextColor(TEXT_COLOR),
+                    Anchor::BottomLeft,
+                    Transform::from_xyz(0., -(index as f32) * 12., 1.),
+                    PIXEL_PERFECT_STATIC_LAYERS,
+                    MenuElement {
+                        cursor_anchor: Transform::from_xyz(-5., 1., 1.),
+                        menu_grid_position: GridTransform::new(0, -(index as i16)),
+                    },
+                    TriggerOnMenuInteract,
+                    DialogChoice(index),
+                ));
+            }
+        }).id();
+    });
+
+    commands.spawn((
+        Transform::from_xyz(0., 0., 30.),
+        Sprite {
+            image: textures.menu_pointer.clone(),
+            anchor: Anchor::BottomLeft,
+            ..default()
+        },
+        MenuCursor {
+            menu_focus: menu_box,
+            menu_grid_position: GridTransform::ZERO,
+        },
+        PIXEL_PERFECT_STATIC_LAYERS,
+        DialogChoiceMenu,
+    ));
+}
+
+/// Blinks the more indicator while a revealed page carries on in the next.
+fn show_more_indicator(
+    time: Res<Time>,
+    current_dialog: Res<CurrentDialog>,
+    current_page_index: Res<CurrentPageIndex>,
+    current_page_text: Res<CurrentPageText>,
+    mut indicator_query: Query<&mut Visibility, With<DialogMoreIndicator>>,
+) {
+    let continued = (*current_dialog).as_ref()
+        .and_then(|dialog| dialog.get(current_page_index.0))
+        .is_some_and(|page| page.continued);
+    let shown = continued
+        && current_page_text.is_revealed()
+        && time.elapsed_secs() % 1. < 0.6;
+    for mut visibility in &mut indicator_query {
+        visibility.set_if_neq(if shown { Visibility::Inherited } else { Visibility::Hidden });
+    }
+}
+
+/// Shows the name plate and portrait of the current page's speaker. The
+/// portrait falls back to the default mood when it has no frame for the page's.
+fn show_speaker(
+    current_dialog: Res<CurrentDialog>,
+    current_page_index: Res<CurrentPageIndex>,
+    dialog_assets: Res<DialogAssets>,
+    speaker_lists: Res<Assets<SpeakerList>>,
+    textures: Res<TextureAssets>,
+    mut display_query: Query<(&mut Visibility, &SpeakerDisplay)>,
+    mut name_query: Query<&mut Text2d, With<DialogSpeakerName>>,
+    mut portrait_query: Query<&mut Sprite, With<DialogPortrait>>,
+) {
+    if !current_page_index.is_changed() {
+        return;
+    }
+    let Some(page) = (*current_dialog).as_ref().and_then(|dialog| dialog.get(current_page_index.0)) else {
+        return;
+    };
+    let speaker = speaker_lists.get(&dialog_assets.speakers)
+        .and_then(|speakers| speakers.get(&page.speaker));
+    let name = speaker.and_then(|speaker| speaker.name.clone());
+    let portrait = speaker
+        .and_then(|speaker| speaker.portrait.as_ref())
+        .and_then(|portrait| Some((
+            textures.get_field::<Handle<Image>>(&portrait.reference)?,
+            textures.get_field::<Handle<TextureAtlasLayout>>(&portrait.atlas_reference)?,
+            portrait.index(&page.mood)?,
+        )));
+
+    for (mut visibility, display) in &mut display_query {
+        let shown = match display {
+            SpeakerDisplay::NamePlate => name.is_some(),
+            SpeakerDisplay::PortraitFrame => portrait.is_some(),
+        };
+        *visibility = if shown { Visibility::Inherited } else { Visibility::Hidden };
+    }
+    for mut text in &mut name_query {
+        **text = name.clone().unwrap_or_default();
+    }
+    if let Some((image, layout, index)) = portrait {
+        for mut sprite in &mut portrait_query {
+            sprite.image = image.clone();
+            sprite.texture_atlas = Some(TextureAtlas {
+                layout: layout.clone(),
+                index,
+            });
+        }
+    }
+}
+
+fn dialog_choice_control(
+    mut events: EventReader<TriggerEvent>,
+    choice_query: Query<&DialogChoice>,
+    current_dialog: Res<CurrentDialog>,
+    current_page_index: Res<CurrentPageIndex>,
+    mut page_events: EventWriter<PageEvent>,
+    mut outcome_events: EventWriter<DialogOutcomeEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+) {
+    let Some(dialog) = (*current_dialog).as_ref() else { return };
+    for event in events.read() {
+        let Ok(DialogChoice(index)) = choice_query.get(event.triggered) else { continue };
+        let choice = &dialog[current_page_index.0].choices[*index];
+        if let Some(outcome) = &choice.outcome {
+            outcome_events.send(DialogOutcomeEvent { outcome: outcome.clone() });
+        }
+        match dialog.page_after_choice(current_page_index.0, choice) {
+            Some(next_index) => { page_events.send(PageEvent(next_index)); }
+            None => next_state.set(state_stack.back()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spans_reveal_at_their_own_pace() {
+        let spans: Vec<Span> = ron::de::from_str(r#"[
+            Span(text: "ab"),
+            Span(text: "cd", speed: 2.0, pausebefore: 300),
+        ]"#).unwrap();
+        let mut page_text = CurrentPageText { spans, current_index: 0 };
+
+        assert_eq!(page_text.next_delay(), Some(Duration::from_millis(50)));
+        page_text.current_index = 2;
+        assert_eq!(page_text.next_delay(), Some(Duration::from_millis(325)));
+        page_text.current_index = 3;
+        assert_eq!(page_text.next_delay(), Some(Duration::from_millis(25)));
+        assert_eq!(page_text.revealed().collect::<Vec<_>>(), ["ab", "c"]);
+
+        page_text.reveal_all();
+        assert!(page_text.is_revealed());
+        assert_eq!(page_text.next_delay(), None);
+    }
+}
@@ -1,5 +1,8 @@
 // pub mod node;
 pub mod mob;
+pub mod battle;
+pub mod storage;
+pub mod options;
 // pub mod connection;
 pub mod scale;
 pub mod grid_transform;
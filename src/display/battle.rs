@@ -0,0 +1,427 @@
+use bevy::prelude::*;
+use bevy::sprite::*;
+use bevy::text::LineBreak;
+use bevy::text::TextBounds;
+
+use crate::battle::*;
+use crate::battle::engine::Combatant;
+use crate::graph::grid_transform::GridTransform;
+use crate::inventory::Inventory;
+use crate::inventory::CAPTURE_ORB;
+use crate::loading::FontAssets;
+use crate::loading::TextureAssets;
+use crate::menu::MenuBox;
+use crate::menu::MenuCursor;
+use crate::menu::MenuElement;
+use crate::menu::TriggerOnMenuInteract;
+use crate::species::SpeciesRegistry;
+use crate::GameState;
+use crate::PIXEL_PERFECT_STATIC_LAYERS;
+use crate::RES_HEIGHT;
+use crate::RES_WIDTH;
+
+pub struct BattleDisplayPlugin;
+
+impl Plugin for BattleDisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(OnExit(GameState::AssetLoading), (
+            init_battle_display,
+        ))
+        .add_systems(OnEnter(GameState::Battle), (
+            enter_battle_display,
+        ))
+        .add_systems(OnExit(GameState::Battle), (
+            exit_battle_display,
+        ))
+        .add_systems(Update, (
+            update_battle_menus,
+            update_battle_message,
+            update_battle_status,
+            update_battle_sprites,
+            update_move_labels,
+            update_orb_label,
+        ).run_if(in_state(GameState::Battle)));
+    }
+}
+
+const TEXT_COLOR: Color = Color::srgb(47. / 255., 76. / 255., 64. / 255.);
+const MENU_ROW_HEIGHT: f32 = 14.;
+const MENU_COLUMN_WIDTH: f32 = 64.;
+
+#[derive(Component)]
+struct BattleScreen;
+
+#[derive(Component)]
+struct BattleActionBox;
+
+#[derive(Component)]
+struct BattleMoveBox;
+
+#[derive(Component)]
+struct BattleCursor;
+
+#[derive(Component)]
+struct BattleMessageText;
+
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum BattleSideDisplay {
+    Player,
+    Opponent,
+}
+
+#[derive(Component)]
+struct BattleStatusText;
+
+#[derive(Component)]
+struct BattleSprite;
+
+fn init_battle_display(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    fonts: Res<FontAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let text_font = TextFont {
+        font: fonts.font.clone(),
+        font_size: 10.,
+        ..Default::default()
+    };
+    let box_size = Vec2::new(RES_WIDTH as f32 - 16., 48.);
+
+    let menu_element = |label: &str, grid_position: GridTransform| (
+        Text2d::new(label.to_string()),
+        text_font.clone(),
+        TextColor(TEXT_COLOR),
+        Anchor::BottomLeft,
+        Transform::from_xyz(
+            grid_position.x as f32 * MENU_COLUMN_WIDTH,
+            grid_position.y as f32 * MENU_ROW_HEIGHT,
+            1.,
+        ),
+        PIXEL_PERFECT_STATIC_LAYERS,
+        MenuElement {
+            cursor_anchor: Transform::from_xyz(-5., 1., 1.),
+            menu_grid_position: grid_position,
+        },
+        TriggerOnMenuInteract,
+    );
+
+    let mut action_box = Entity::PLACEHOLDER;
+
+    commands.spawn((
+        Name::new("Battle Screen".to_string()),
+        Transform::from_xyz(0., 0., 20.),
+        Visibility::Hidden,
+        PIXEL_PERFECT_STATIC_LAYERS,
+        BattleScreen,
+    )).with_children(|builder| {
+        builder.spawn((
+            Mesh2d(meshes.add(Rectangle::new(
+                RES_WIDTH as f32,
+                RES_HEIGHT as f32,
+            ))),
+            MeshMaterial2d(materials.add(Color::srgb_u8(224, 240, 232))),
+            Transform::from_xyz(
+                RES_WIDTH as f32 / 2.0,
+                RES_HEIGHT as f32 / 2.0,
+                0.,
+            ),
+            PIXEL_PERFECT_STATIC_LAYERS,
+        ));
+
+        for (side, status_position, sprite_position) in [
+            (BattleSideDisplay::Opponent, Vec2::new(8., 136.), Vec2::new(120., 104.)),
+            (BattleSideDisplay::Player, Vec2::new(80., 88.), Vec2::new(36., 72.)),
+        ] {
+            builder.spawn((
+                Text2d::new("".to_string()),
+                text_font.clone(),
+                TextColor(TEXT_COLOR),
+                Anchor::TopLeft,
+                Transform::from_translation(status_position.extend(1.)),
+                PIXEL_PERFECT_STATIC_LAYERS,
+                BattleStatusText,
+                side,
+            ));
+            builder.spawn((
+                Sprite::default(),
+                Transform::from_translation(sprite_position.extend(1.))
+                    .with_scale(Vec3::new(2., 2., 1.)),
+                PIXEL_PERFECT_STATIC_LAYERS,
+                BattleSprite,
+                side,
+            ));
+        }
+
+        builder.spawn((
+            Transform::from_xyz(8., 8., 2.),
+            Sprite {
+                image: textures.dialog_box.clone(),
+                custom_size: Some(box_size),
+                anchor: Anchor::BottomLeft,
+                image_mode: SpriteImageMode::Sliced(TextureSlicer {
+                    border: BorderRect::square(8.),
+                    center_scale_mode: SliceScaleMode::Stretch,
+                    sides_scale_mode: SliceScaleMode::Stretch,
+                    max_corner_scale: 1.0,
+                }),
+                ..default()
+            },
+            PIXEL_PERFECT_STATIC_LAYERS,
+        )).with_children(|builder| {
+            builder.spawn((
+                Text2d::new("".to_string()),
+                text_font.clone(),
+                TextColor(TEXT_COLOR),
+                TextLayout {
+                    justify: JustifyText::Left,
+                    linebreak: LineBreak::WordBoundary,
+                },
+                Anchor::TopLeft,
+                TextBounds {
+                    width: Some(box_size.x - 16.),
+                    height: Some(box_size.y - 4.),
+                },
+                Transform::from_xyz(8., box_size.y - 2., 1.),
+                PIXEL_PERFECT_STATIC_LAYERS,
+                BattleMessageText,
+            ));
+
+            action_box = builder.spawn((
+                Transform::from_xyz(24., 26., 1.),
+                Visibility::Hidden,
+                PIXEL_PERFECT_STATIC_LAYERS,
+                MenuBox::default(),
+                BattleActionBox,
+            )).with_children(|builder| {
+                builder.spawn((
+                    menu_element("Fight", GridTransform::new(0, 0)),
+                    BattleMenuAction::Fight,
+                ));
+                builder.spawn((
+                    menu_element("Run", GridTransform::new(1, 0)),
+                    BattleMenuAction::Run,
+                ));
+                builder.spawn((
+                    menu_element("Orb", GridTransform::new(0, -1)),
+                    BattleMenuAction::Orb,
+                ));
+            }).id();
+
+            builder.spawn((
+                Transform::from_xyz(12., 30., 1.),
+                Visibility::Hidden,
+                PIXEL_PERFECT_STATIC_LAYERS,
+                MenuBox::default(),
+                BattleMoveBox,
+            )).with_children(|builder| {
+                for slot in 0..4 {
+                    let grid_position = GridTransform::new(slot as i16 % 2, -(slot as i16 / 2));
+                    builder.spawn((
+                        menu_element("-", grid_position),
+                        BattleMoveSlot(slot),
+                    ));
+                }
+                builder.spawn((
+                    menu_element("Back", GridTransform::new(1, -2)),
+                    BattleMenuAction::Back,
+                ));
+            });
+        });
+    });
+
+    commands.spawn((
+        Transform::from_xyz(0., 0., 30.),
+        Sprite {
+            image: textures.menu_pointer.clone(),
+            anchor: Anchor::BottomLeft,
+            ..default()
+        },
+        Visibility::Hidden,
+        MenuCursor {
+            menu_focus: action_box,
+            menu_grid_position: GridTransform::ZERO,
+        },
+        PIXEL_PERFECT_STATIC_LAYERS,
+        BattleCursor,
+    ));
+}
+
+fn enter_battle_display(
+    mut screen_query: Query<&mut Visibility, With<BattleScreen>>,
+) {
+    for mut visibility in &mut screen_query {
+        *visibility = Visibility::Inherited;
+    }
+}
+
+fn exit_battle_display(
+    mut screen_query: Query<&mut Visibility, Or<(With<BattleScreen>, With<BattleCursor>)>>,
+) {
+    for mut visibility in &mut screen_query {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+/// Shows the menu matching the current [`BattlePhase`] and points the cursor at it.
+fn update_battle_menus(
+    phase: Res<BattlePhase>,
+    mut cursor_query: Query<(&mut MenuCursor, &mut Visibility), With<BattleCursor>>,
+    mut action_box_query: Query<(Entity, &mut Visibility), (With<BattleActionBox>, Without<BattleCursor>, Without<BattleMoveBox>)>,
+    mut move_box_query: Query<(Entity, &mut Visibility), (With<BattleMoveBox>, Without<BattleCursor>, Without<BattleActionBox>)>,
+    mut message_query: Query<&mut Visibility, (With<BattleMessageText>, Without<BattleCursor>, Without<BattleActionBox>, Without<BattleMoveBox>)>,
+) {
+    if !phase.is_changed() {
+        return;
+    }
+    let (Ok((action_box, mut action_visibility)), Ok((move_box, mut move_visibility))) =
+        (action_box_query.get_single_mut(), move_box_query.get_single_mut())
+    else {
+        return;
+    };
+
+    let (focus, show_actions, show_moves) = match *phase {
+        BattlePhase::SelectAction => (Some(action_box), true, false),
+        BattlePhase::SelectMove => (Some(move_box), false, true),
+        BattlePhase::Messages | BattlePhase::Finished => (None, false, false),
+    };
+    let hidden = |shown: bool| if shown { Visibility::Inherited } else { Visibility::Hidden };
+    *action_visibility = hidden(show_actions);
+    *move_visibility = hidden(show_moves);
+    for mut visibility in &mut message_query {
+        *visibility = hidden(focus.is_none());
+    }
+
+    for (mut cursor, mut visibility) in &mut cursor_query {
+        match focus {
+            Some(menu_box) => {
+                cursor.menu_focus = menu_box;
+                cursor.menu_grid_position = GridTransform::ZERO;
+                *visibility = Visibility::Inherited;
+            }
+            None => {
+                *visibility = Visibility::Hidden;
+            }
+        }
+    }
+}
+
+fn update_battle_message(
+    messages: Res<BattleMessages>,
+    mut text_query: Query<&mut Text2d, With<BattleMessageText>>,
+) {
+    if !messages.is_changed() {
+        return;
+    }
+    let text = messages.current()
+        .map(|message| message.text.clone())
+        .unwrap_or_default();
+    for mut message_text in &mut text_query {
+        **message_text = text.clone();
+    }
+}
+
+fn displayed_combatant<'a>(
+    battle: &'a CurrentBattle,
+    view: &BattleView,
+    side: BattleSideDisplay,
+) -> Option<(&'a Combatant, u16)> {
+    let battle = battle.0.as_ref()?;
+    let (battle_side, active, hp) = match side {
+        BattleSideDisplay::Player => (&battle.player, view.player_active, &view.player_hp),
+        BattleSideDisplay::Opponent => (&battle.opponent, view.opponent_active, &view.opponent_hp),
+    };
+    let combatant = battle_side.daemons.get(active)?;
+    Some((combatant, hp.get(active).copied().unwrap_or(combatant.hp)))
+}
+
+fn update_battle_status(
+    current_battle: Res<CurrentBattle>,
+    messages: Res<BattleMessages>,
+    mut text_query: Query<(&mut Text2d, &BattleSideDisplay), With<BattleStatusText>>,
+) {
+    if !messages.is_changed() && !current_battle.is_changed() {
+        return;
+    }
+    for (mut text, side) in &mut text_query {
+        if let Some((combatant, hp)) = displayed_combatant(&current_battle, messages.view(), *side) {
+            **text = format!(
+                "{} Lv{}\nHP {}/{}",
+                combatant.name,
+                combatant.level,
+                hp,
+                combatant.stats.hp,
+            );
+        }
+    }
+}
+
+fn update_battle_sprites(
+    current_battle: Res<CurrentBattle>,
+    messages: Res<BattleMessages>,
+    registry: Res<SpeciesRegistry>,
+    textures: Res<TextureAssets>,
+    mut sprite_query: Query<(&mut Sprite, &mut Visibility, &BattleSideDisplay), With<BattleSprite>>,
+) {
+    if !messages.is_changed() && !current_battle.is_changed() {
+        return;
+    }
+    for (mut sprite, mut visibility, side) in &mut sprite_query {
+        let Some((combatant, hp)) = displayed_combatant(&current_battle, messages.view(), *side) else {
+            continue;
+        };
+        *visibility = if hp == 0 { Visibility::Hidden } else { Visibility::Inherited };
+        let Some(species) = registry.species.get(&combatant.species) else {
+            continue;
+        };
+        let (Some(image), Some(layout)) = (
+            textures.get_field::<Handle<Image>>(&species.sprite.reference),
+            textures.get_field::<Handle<TextureAtlasLayout>>(&species.sprite.atlas_reference),
+        ) else {
+            continue;
+        };
+        sprite.image = image.clone();
+        sprite.texture_atlas = Some(TextureAtlas {
+            layout: layout.clone(),
+            index: species.sprite.index,
+        });
+    }
+}
+
+fn update_move_labels(
+    current_battle: Res<CurrentBattle>,
+    registry: Res<SpeciesRegistry>,
+    mut label_query: Query<(&mut Text2d, &BattleMoveSlot)>,
+) {
+    if !current_battle.is_changed() {
+        return;
+    }
+    let Some(battle) = current_battle.0.as_ref() else {
+        return;
+    };
+    let moves = &battle.player.active().moves;
+    for (mut text, slot) in &mut label_query {
+        **text = match moves.get(**slot) {
+            Some(move_slot) => registry.moves.get(&move_slot.id)
+                .map(|data| data.name.clone())
+                .unwrap_or_else(|| move_slot.id.clone()),
+            None => "-".to_string(),
+        };
+    }
+}
+
+fn update_orb_label(
+    inventory: Res<Inventory>,
+    mut label_query: Query<(&mut Text2d, &BattleMenuAction)>,
+) {
+    if !inventory.is_changed() {
+        return;
+    }
+    for (mut text, action) in &mut label_query {
+        if *action == BattleMenuAction::Orb {
+            **text = format!("Orb x{}", inventory.count(CAPTURE_ORB));
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use bevy::prelude::*;
+use bevy::sprite::*;
+
+use crate::control::key_label;
+use crate::control::GameControl;
+use crate::control::InputMap;
+use crate::graph::grid_transform::GridTransform;
+use crate::loading::FontAssets;
+use crate::loading::TextureAssets;
+use crate::menu::MenuBox;
+use crate::menu::MenuCursor;
+use crate::menu::MenuElement;
+use crate::menu::TriggerOnMenuInteract;
+use crate::options::*;
+use crate::text_loading::Language;
+use crate::GameState;
+use crate::PIXEL_PERFECT_STATIC_LAYERS;
+use crate::RES_HEIGHT;
+use crate::RES_WIDTH;
+
+pub struct OptionsDisplayPlugin;
+
+impl Plugin for OptionsDisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(OnExit(GameState::AssetLoading), (
+            init_options_display,
+        ))
+        .add_systems(OnEnter(GameState::Options), (
+            enter_options_display,
+        ))
+        .add_systems(OnExit(GameState::Options), (
+            exit_options_display,
+        ))
+        .add_systems(Update, (
+            update_key_labels,
+            update_language_label,
+            update_options_text,
+        ).run_if(in_state(GameState::Options)));
+    }
+}
+
+const TEXT_COLOR: Color = Color::srgb(47. / 255., 76. / 255., 64. / 255.);
+const ROW_HEIGHT: f32 = 10.;
+const SLOT_COLUMN_WIDTH: f32 = 44.;
+
+#[derive(Component)]
+struct OptionsScreen;
+
+#[derive(Component)]
+struct OptionsCursor;
+
+#[derive(Component)]
+struct OptionsMessageText;
+
+fn init_options_display(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    fonts: Res<FontAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let text_font = TextFont {
+        font: fonts.font.clone(),
+        font_size: 10.,
+        ..Default::default()
+    };
+
+    let text = |label: &str, position: Vec2| (
+        Text2d::new(label.to_string()),
+        text_font.clone(),
+        TextColor(TEXT_COLOR),
+        Anchor::BottomLeft,
+        Transform::from_translation(position.extend(1.)),
+        PIXEL_PERFECT_STATIC_LAYERS,
+    );
+
+    let menu_element = |grid_position: GridTransform| (
+        MenuElement {
+            cursor_anchor: Transform::from_xyz(-5., 1., 1.),
+            menu_grid_position: grid_position,
+        },
+        TriggerOnMenuInteract,
+    );
+
+    let mut menu_box = Entity::PLACEHOLDER;
+
+    commands.spawn((
+        Name::new("Options Screen".to_string()),
+        Transform::from_xyz(0., 0., 20.),
+        Visibility::Hidden,
+        PIXEL_PERFECT_STATIC_LAYERS,
+        OptionsScreen,
+    )).with_children(|builder| {
+        builder.spawn((
+            Mesh2d(meshes.add(Rectangle::new(
+                RES_WIDTH as f32,
+                RES_HEIGHT as f32,
+            ))),
+            MeshMaterial2d(materials.add(Color::srgb_u8(224, 240, 232))),
+            Transform::from_xyz(
+                RES_WIDTH as f32 / 2.0,
+                RES_HEIGHT as f32 / 2.0,
+                0.,
+            ),
+            PIXEL_PERFECT_STATIC_LAYERS,
+        ));
+
+        builder.spawn(text("Controls", Vec2::new(8., 130.)));
+
+        builder.spawn((
+            text("", Vec2::new(8., 8.)),
+            OptionsMessageText,
+        ));
+
+        // One row per control with its key slots to the right,
+        // resetting and leaving below.
+        menu_box = builder.spawn((
+            Transform::from_xyz(8., 116., 1.),
+            PIXEL_PERFECT_STATIC_LAYERS,
+            MenuBox::default(),
+        )).with_children(|builder| {
+            let row_y = |row: usize| -(row as f32) * ROW_HEIGHT;
+            for (row, control) in GameControl::ALL.into_iter().enumerate() {
+                builder.spawn(text(control.label(), Vec2::new(0., row_y(row))));
+                for index in 0..KEY_SLOTS {
+                    builder.spawn((
+                        text("-", Vec2::new(60. + index as f32 * SLOT_COLUMN_WIDTH, row_y(row))),
+                        menu_element(GridTransform::new(index as i16, -(row as i16))),
+                        KeyBindingSlot { control, index },
+                    ));
+                }
+            }
+            let language_row = GameControl::ALL.len();
+            builder.spawn(text("Language", Vec2::new(0., row_y(language_row))));
+            builder.spawn((
+                text("", Vec2::new(60., row_y(language_row))),
+                menu_element(GridTransform::new(0, -(language_row as i16))),
+                OptionsMenuAction::Language,
+            ));
+            let last_row = language_row + 1;
+            builder.spawn((
+                text("Reset", Vec2::new(60., row_y(last_row))),
+                menu_element(GridTransform::new(0, -(last_row as i16))),
+                OptionsMenuAction::ResetDefaults,
+            ));
+            builder.spawn((
+                text("Back", Vec2::new(60. + SLOT_COLUMN_WIDTH, row_y(last_row))),
+                menu_element(GridTransform::new(1, -(last_row as i16))),
+                OptionsMenuAction::Back,
+            ));
+        }).id();
+    });
+
+    commands.spawn((
+        Transform::from_xyz(0., 0., 30.),
+        Sprite {
+            image: textures.menu_pointer.clone(),
+            anchor: Anchor::BottomLeft,
+            ..default()
+        },
+        Visibility::Hidden,
+        MenuCursor {
+            menu_focus: menu_box,
+            menu_grid_position: GridTransform::ZERO,
+        },
+        PIXEL_PERFECT_STATIC_LAYERS,
+        OptionsCursor,
+    ));
+}
+
+fn enter_options_display(
+    mut screen_query: Query<&mut Visibility, Or<(With<OptionsScreen>, With<OptionsCursor>)>>,
+    mut cursor_query: Query<&mut MenuCursor, With<OptionsCursor>>,
+) {
+    for mut visibility in &mut screen_query {
+        *visibility = Visibility::Inherited;
+    }
+    for mut cursor in &mut cursor_query {
+        cursor.menu_grid_position = GridTransform::ZERO;
+    }
+}
+
+fn exit_options_display(
+    mut screen_query: Query<&mut Visibility, Or<(With<OptionsScreen>, With<OptionsCursor>)>>,
+) {
+    for mut visibility in &mut screen_query {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+fn update_key_labels(
+    input_map: Res<InputMap>,
+    view: Res<OptionsView>,
+    mut label_query: Query<(&mut Text2d, &KeyBindingSlot)>,
+) {
+    if !input_map.is_changed() && !view.is_changed() {
+        return;
+    }
+    for (mut text, slot) in &mut label_query {
+        **text = if view.listening == Some(*slot) {
+            "...".to_string()
+        } else {
+            input_map.keys_for(slot.control).get(slot.index)
+                .map(|key| key_label(*key))
+                .unwrap_or("-".to_string())
+        };
+    }
+}
+
+fn update_language_label(
+    language: Res<Language>,
+    mut label_query: Query<(&mut Text2d, &OptionsMenuAction)>,
+) {
+    if !language.is_changed() {
+        return;
+    }
+    for (mut text, action) in &mut label_query {
+        if *action == OptionsMenuAction::Language {
+            **text = language.name().to_string();
+        }
+    }
+}
+
+/// Shows the latest message, and hides the cursor while waiting for a key so
+/// the menu doesn't react to it.
+fn update_options_text(
+    view: Res<OptionsView>,
+    mut message_query: Query<&mut Text2d, With<OptionsMessageText>>,
+    mut cursor_query: Query<&mut Visibility, With<OptionsCursor>>,
+) {
+    if !view.is_changed() {
+        return;
+    }
+    for mut text in &mut message_query {
+        **text = view.message.clone();
+    }
+    for mut visibility in &mut cursor_query {
+        *visibility = if view.listening.is_some() {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+}
@@ -0,0 +1,289 @@
+use bevy::prelude::*;
+use bevy::sprite::*;
+
+use crate::graph::grid_transform::GridTransform;
+use crate::loading::FontAssets;
+use crate::loading::TextureAssets;
+use crate::menu::MenuBox;
+use crate::menu::MenuCursor;
+use crate::menu::MenuElement;
+use crate::menu::TriggerOnMenuInteract;
+use crate::party::DaemonInstance;
+use crate::party::Party;
+use crate::party::PARTY_SIZE;
+use crate::species::SpeciesRegistry;
+use crate::storage::*;
+use crate::GameState;
+use crate::PIXEL_PERFECT_STATIC_LAYERS;
+use crate::RES_HEIGHT;
+use crate::RES_WIDTH;
+
+pub struct StorageDisplayPlugin;
+
+impl Plugin for StorageDisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(OnExit(GameState::AssetLoading), (
+            init_storage_display,
+        ))
+        .add_systems(OnEnter(GameState::Storage), (
+            enter_storage_display,
+        ))
+        .add_systems(OnExit(GameState::Storage), (
+            exit_storage_display,
+        ))
+        .add_systems(Update, (
+            update_storage_labels,
+            update_storage_text,
+        ).run_if(in_state(GameState::Storage)));
+    }
+}
+
+const TEXT_COLOR: Color = Color::srgb(47. / 255., 76. / 255., 64. / 255.);
+const SLOT_ROW_HEIGHT: f32 = 14.;
+const SLOT_COLUMN_WIDTH: f32 = 28.;
+const BOX_COLUMNS: usize = 4;
+/// Characters of a daemon's name that fit in one slot.
+const SLOT_LABEL_LENGTH: usize = 3;
+
+#[derive(Component)]
+struct StorageScreen;
+
+#[derive(Component)]
+struct StorageCursor;
+
+#[derive(Component)]
+struct StorageTitleText;
+
+#[derive(Component)]
+struct StorageMessageText;
+
+fn init_storage_display(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    fonts: Res<FontAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let text_font = TextFont {
+        font: fonts.font.clone(),
+        font_size: 10.,
+        ..Default::default()
+    };
+
+    let menu_element = |label: &str, grid_position: GridTransform, position: Vec2| (
+        Text2d::new(label.to_string()),
+        text_font.clone(),
+        TextColor(TEXT_COLOR),
+        Anchor::BottomLeft,
+        Transform::from_translation(position.extend(1.)),
+        PIXEL_PERFECT_STATIC_LAYERS,
+        MenuElement {
+            cursor_anchor: Transform::from_xyz(-5., 1., 1.),
+            menu_grid_position: grid_position,
+        },
+        TriggerOnMenuInteract,
+    );
+
+    let mut menu_box = Entity::PLACEHOLDER;
+
+    commands.spawn((
+        Name::new("Storage Screen".to_string()),
+        Transform::from_xyz(0., 0., 20.),
+        Visibility::Hidden,
+        PIXEL_PERFECT_STATIC_LAYERS,
+        StorageScreen,
+    )).with_children(|builder| {
+        builder.spawn((
+            Mesh2d(meshes.add(Rectangle::new(
+                RES_WIDTH as f32,
+                RES_HEIGHT as f32,
+            ))),
+            MeshMaterial2d(materials.add(Color::srgb_u8(224, 240, 232))),
+            Transform::from_xyz(
+                RES_WIDTH as f32 / 2.0,
+                RES_HEIGHT as f32 / 2.0,
+                0.,
+            ),
+            PIXEL_PERFECT_STATIC_LAYERS,
+        ));
+
+        builder.spawn((
+            Text2d::new("".to_string()),
+            text_font.clone(),
+            TextColor(TEXT_COLOR),
+            Anchor::BottomLeft,
+            Transform::from_xyz(72., 126., 1.),
+            PIXEL_PERFECT_STATIC_LAYERS,
+            StorageTitleText,
+        ));
+
+        builder.spawn((
+            Text2d::new("".to_string()),
+            text_font.clone(),
+            TextColor(TEXT_COLOR),
+            Anchor::BottomLeft,
+            Transform::from_xyz(8., 8., 1.),
+            PIXEL_PERFECT_STATIC_LAYERS,
+            StorageMessageText,
+        ));
+
+        // The party runs down the left column, the box fills a grid to its right,
+        // with box switching above and sorting and exit below.
+        menu_box = builder.spawn((
+            Transform::from_xyz(12., 108., 1.),
+            PIXEL_PERFECT_STATIC_LAYERS,
+            MenuBox::default(),
+        )).with_children(|builder| {
+            let slot_position = |column: usize, row: usize| Vec2::new(
+                if column == 0 { 0. } else { 20. + column as f32 * SLOT_COLUMN_WIDTH },
+                -(row as f32) * SLOT_ROW_HEIGHT,
+            );
+            for index in 0..PARTY_SIZE {
+                builder.spawn((
+                    menu_element("-", GridTransform::new(0, -(index as i16)), slot_position(0, index)),
+                    StorageSlot::Party(index),
+                ));
+            }
+            for index in 0..BOX_SLOTS {
+                let (column, row) = (1 + index % BOX_COLUMNS, index / BOX_COLUMNS);
+                builder.spawn((
+                    menu_element(
+                        "-",
+                        GridTransform::new(column as i16, -(row as i16)),
+                        slot_position(column, row),
+                    ),
+                    StorageSlot::Box(index),
+                ));
+            }
+            builder.spawn((
+                menu_element("<", GridTransform::new(1, 1), Vec2::new(48., SLOT_ROW_HEIGHT + 4.)),
+                StorageMenuAction::PreviousBox,
+            ));
+            builder.spawn((
+                menu_element(">", GridTransform::new(4, 1), Vec2::new(132., SLOT_ROW_HEIGHT + 4.)),
+                StorageMenuAction::NextBox,
+            ));
+            builder.spawn((
+                menu_element("Sort", GridTransform::new(1, -5), slot_position(1, 5)),
+                StorageMenuAction::Sort,
+            ));
+            builder.spawn((
+                menu_element("Exit", GridTransform::new(3, -5), slot_position(3, 5)),
+                StorageMenuAction::Exit,
+            ));
+        }).id();
+    });
+
+    commands.spawn((
+        Transform::from_xyz(0., 0., 30.),
+        Sprite {
+            image: textures.menu_pointer.clone(),
+            anchor: Anchor::BottomLeft,
+            ..default()
+        },
+        Visibility::Hidden,
+        MenuCursor {
+            menu_focus: menu_box,
+            menu_grid_position: GridTransform::ZERO,
+        },
+        PIXEL_PERFECT_STATIC_LAYERS,
+        StorageCursor,
+    ));
+}
+
+fn enter_storage_display(
+    mut screen_query: Query<&mut Visibility, Or<(With<StorageScreen>, With<StorageCursor>)>>,
+    mut cursor_query: Query<&mut MenuCursor, With<StorageCursor>>,
+) {
+    for mut visibility in &mut screen_query {
+        *visibility = Visibility::Inherited;
+    }
+    for mut cursor in &mut cursor_query {
+        cursor.menu_grid_position = GridTransform::ZERO;
+    }
+}
+
+fn exit_storage_display(
+    mut screen_query: Query<&mut Visibility, Or<(With<StorageScreen>, With<StorageCursor>)>>,
+) {
+    for mut visibility in &mut screen_query {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+fn daemon_at<'a>(
+    slot: StorageSlot,
+    party: &'a Party,
+    storage: &'a DaemonStorage,
+    view: &StorageView,
+) -> Option<&'a DaemonInstance> {
+    match slot {
+        StorageSlot::Party(index) => party.members().get(index),
+        StorageSlot::Box(index) => storage.boxes[view.current_box].slots[index].as_ref(),
+    }
+}
+
+fn update_storage_labels(
+    party: Res<Party>,
+    storage: Res<DaemonStorage>,
+    view: Res<StorageView>,
+    registry: Res<SpeciesRegistry>,
+    mut label_query: Query<(&mut Text2d, &StorageSlot)>,
+) {
+    if !party.is_changed() && !storage.is_changed() && !view.is_changed() {
+        return;
+    }
+    for (mut text, slot) in &mut label_query {
+        let label = match daemon_at(*slot, &party, &storage, &view) {
+            Some(daemon) => registry.species.get(&daemon.species)
+                .map(|species| daemon.name(species))
+                .unwrap_or(&daemon.species)
+                .chars()
+                .take(SLOT_LABEL_LENGTH)
+                .collect(),
+            None => "-".to_string(),
+        };
+        **text = if view.held == Some(*slot) {
+            format!("*{}", label)
+        } else {
+            label
+        };
+    }
+}
+
+/// Box name on top; at the bottom, the hovered daemon or the latest message.
+fn update_storage_text(
+    party: Res<Party>,
+    storage: Res<DaemonStorage>,
+    view: Res<StorageView>,
+    registry: Res<SpeciesRegistry>,
+    cursor_query: Query<&MenuCursor, (With<StorageCursor>, Changed<MenuCursor>)>,
+    menu_box_query: Query<&MenuBox>,
+    slot_query: Query<&StorageSlot>,
+    mut title_query: Query<&mut Text2d, (With<StorageTitleText>, Without<StorageMessageText>)>,
+    mut message_query: Query<&mut Text2d, (With<StorageMessageText>, Without<StorageTitleText>)>,
+) {
+    if !view.is_changed() && !storage.is_changed() && cursor_query.is_empty() {
+        return;
+    }
+    let hovered = cursor_query.get_single().ok()
+        .and_then(|cursor| menu_box_query.get(cursor.menu_focus).ok()
+            .and_then(|menu_box| menu_box.elements_index.get(&cursor.menu_grid_position)))
+        .and_then(|element| slot_query.get(*element).ok())
+        .and_then(|slot| daemon_at(*slot, &party, &storage, &view));
+
+    for mut text in &mut title_query {
+        **text = storage.boxes[view.current_box].name.clone();
+    }
+    let message = match hovered {
+        Some(daemon) if view.held.is_none() => match registry.species.get(&daemon.species) {
+            Some(species) => format!("{} Lv{}", daemon.name(species), daemon.level),
+            None => daemon.species.clone(),
+        },
+        _ => view.message.clone(),
+    };
+    for mut text in &mut message_query {
+        **text = message.clone();
+    }
+}
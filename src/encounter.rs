@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::battle::engine::BattleKind;
+use crate::battle::DaemonSpec;
+use crate::battle::StartBattleEvent;
+use crate::helpers::ron_asset::RonAssetLoader;
+use crate::loading::DaemonAssets;
+use crate::map::TriggerOnMoveOnto;
+use crate::mob::MobSteppedEvent;
+use crate::mob::TriggerOnMoveOntoEvent;
+use crate::player::Player;
+use crate::rng::GameRng;
+use crate::GameState;
+
+pub struct EncounterPlugin;
+
+impl Plugin for EncounterPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(Update, (
+            roll_wild_encounters,
+            count_down_repel,
+        ).chain().run_if(in_state(GameState::Playing)))
+        .init_asset::<EncounterTables>()
+        .register_asset_loader(RonAssetLoader::<EncounterTables>::new(&["encounters.ron"]))
+        .init_resource::<EncounterSettings>()
+        .register_type::<EncounterSettings>()
+        .register_type::<EncounterZone>();
+    }
+}
+
+/// Tiles where wild daemons can appear, authored in Tiled.
+///
+/// `table` names an entry in `daemons/encounters.encounters.ron`, and `rate` is
+/// the chance of an encounter on each step the player takes onto the tile.
+#[derive(Component, Default, Debug, Reflect)]
+#[reflect(Component, Default)]
+#[require(TriggerOnMoveOnto)]
+pub struct EncounterZone {
+    pub table: String,
+    pub rate: f32,
+}
+
+#[derive(Reflect, Debug, Deserialize, Clone)]
+pub struct EncounterEntry {
+    pub species: String,
+    pub min_level: u8,
+    pub max_level: u8,
+    pub weight: u32,
+}
+
+/// Encounter tables keyed by the name used in [`EncounterZone::table`].
+#[derive(Asset, Reflect, Debug, Deserialize, Deref, DerefMut, Clone)]
+pub struct EncounterTables(HashMap<String, Vec<EncounterEntry>>);
+
+#[derive(Resource, Reflect, Debug)]
+pub struct EncounterSettings {
+    /// Turns wild encounters off entirely, e.g. for cutscenes.
+    pub enabled: bool,
+    /// Steps left during which no wild daemons appear. Every step counts,
+    /// not just those in encounter zones.
+    pub repel_steps: u32,
+}
+
+impl Default for EncounterSettings {
+    fn default() -> Self {
+        EncounterSettings {
+            enabled: true,
+            repel_steps: 0,
+        }
+    }
+}
+
+/// Rolls a single step in a zone with the given rate and table.
+///
+/// Returns the daemon to fight, picked by weight with a uniformly random level.
+pub fn roll_encounter(
+    rate: f32,
+    table: &[EncounterEntry],
+    rng: &mut impl Rng,
+) -> Option<DaemonSpec> {
+    if !rng.gen_bool(rate.clamp(0.0, 1.0) as f64) {
+        return None;
+    }
+    let total: u32 = table.iter().map(|entry| entry.weight).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut pick = rng.gen_range(0..total);
+    let entry = table.iter().find(|entry| {
+        if pick < entry.weight {
+            return true;
+        }
+        pick -= entry.weight;
+        false
+    })?;
+    let (low, high) = (
+        entry.min_level.min(entry.max_level),
+        entry.min_level.max(entry.max_level),
+    );
+    Some(DaemonSpec {
+        species: entry.species.clone(),
+        level: rng.gen_range(low..=high),
+    })
+}
+
+fn roll_wild_encounters(
+    mut events: EventReader<TriggerOnMoveOntoEvent>,
+    player_query: Query<(), With<Player>>,
+    zone_query: Query<&EncounterZone>,
+    settings: Res<EncounterSettings>,
+    daemon_assets: Res<DaemonAssets>,
+    encounter_tables: Res<Assets<EncounterTables>>,
+    mut rng: ResMut<GameRng>,
+    mut battle_events: EventWriter<StartBattleEvent>,
+) {
+    for event in events.read() {
+        if !player_query.contains(event.moved) {
+            continue;
+        }
+        let Ok(zone) = zone_query.get(event.triggered) else {
+            continue;
+        };
+        if !settings.enabled || settings.repel_steps > 0 {
+            continue;
+        }
+        let Some(table) = encounter_tables.get(&daemon_assets.encounters)
+            .and_then(|tables| tables.get(&zone.table))
+        else {
+            warn!("Unknown encounter table: {}", zone.table);
+            continue;
+        };
+
+        if let Some(opponent) = roll_encounter(zone.rate, table, &mut **rng) {
+            battle_events.send(StartBattleEvent {
+                kind: BattleKind::Wild,
+                opponents: vec![opponent],
+                opponent_name: None,
+            });
+            return;
+        }
+    }
+}
+
+fn count_down_repel(
+    mut events: EventReader<MobSteppedEvent>,
+    player_query: Query<(), With<Player>>,
+    mut settings: ResMut<EncounterSettings>,
+) {
+    for event in events.read() {
+        if player_query.contains(event.entity) {
+            settings.repel_steps = settings.repel_steps.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::control::GameControl;
+    use crate::graph::grid_transform::GridTransform;
+    use crate::mob::Mob;
+    use crate::mob::MobMoveEvent;
+    use crate::testing::TestApp;
+    use crate::testing::STEP_TICKS;
+
+    fn table() -> Vec<EncounterEntry> {
+        vec![
+            EncounterEntry { species: "mossling".to_string(), min_level: 2, max_level: 4, weight: 3 },
+            EncounterEntry { species: "pebblit".to_string(), min_level: 3, max_level: 3, weight: 1 },
+        ]
+    }
+
+    #[test]
+    fn rolls_stay_within_table() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..200 {
+            let spec = roll_encounter(1.0, &table(), &mut rng).unwrap();
+            match spec.species.as_str() {
+                "mossling" => assert!((2..=4).contains(&spec.level)),
+                "pebblit" => assert_eq!(spec.level, 3),
+                other => panic!("unexpected species {other}"),
+            }
+        }
+    }
+
+    #[test]
+    fn zero_rate_and_empty_table_never_encounter() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..200 {
+            assert!(roll_encounter(0.0, &table(), &mut rng).is_none());
+            assert!(roll_encounter(1.0, &[], &mut rng).is_none());
+        }
+    }
+
+    #[test]
+    fn same_seed_same_encounters() {
+        let roll = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..20)
+                .map(|_| roll_encounter(0.3, &table(), &mut rng).map(|spec| (spec.species, spec.level)))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(roll(42), roll(42));
+    }
+
+    #[test]
+    fn repel_counts_every_step() {
+        let mut app = TestApp::new();
+        app.app
+        .insert_resource(EncounterSettings { enabled: true, repel_steps: 3 })
+        .add_systems(Update, count_down_repel);
+        app.spawn_player(GridTransform::ZERO);
+        let other = app.spawn_at(GridTransform::new(5, 5), Mob);
+
+        app.hold(GameControl::Right, STEP_TICKS * 2);
+        app.app.world_mut().send_event(MobMoveEvent { entity: other, movement: GridTransform::EAST });
+        app.tick(1);
+        assert_eq!(app.app.world().resource::<EncounterSettings>().repel_steps, 1);
+
+        app.hold(GameControl::Right, STEP_TICKS * 2);
+        assert_eq!(app.app.world().resource::<EncounterSettings>().repel_steps, 0);
+    }
+}
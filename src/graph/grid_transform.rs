@@ -1,4 +1,6 @@
 use bevy::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
 use std::ops::{Add, AddAssign, Sub, Neg};
 use std::convert::TryFrom;
 use bevy_ecs_tilemap::prelude::*;
@@ -7,7 +9,7 @@ use bevy_ecs_tilemap::prelude::*;
 
 pub const SCALE_FACTOR: f32 = 16.0;
 
-#[derive(Component, Debug, PartialEq, Eq, Hash, Default, Clone, Copy, Reflect)]
+#[derive(Component, Debug, PartialEq, Eq, Hash, Default, Clone, Copy, Reflect, Serialize, Deserialize)]
 pub struct GridTransform {
     pub x: i16,
     pub y: i16,
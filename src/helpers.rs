@@ -1,3 +1,4 @@
 // pub mod camera;
 // pub mod ldtk;
 // pub mod tiled;
+pub mod ron_asset;
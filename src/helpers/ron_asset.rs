@@ -0,0 +1,61 @@
+use std::marker::PhantomData;
+
+use bevy::prelude::*;
+use bevy::asset::{io::Reader, AssetLoader, LoadContext};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
+
+/// Loads any deserializable [`Asset`] from a RON file.
+///
+/// Game data files use compound extensions (e.g. `emberkit.species.ron`) so each
+/// data type gets its own loader without clashing with the plain `.ron` locale files.
+pub struct RonAssetLoader<A> {
+    extensions: &'static [&'static str],
+    _marker: PhantomData<fn() -> A>,
+}
+
+impl<A> RonAssetLoader<A> {
+    pub fn new(extensions: &'static [&'static str]) -> Self {
+        RonAssetLoader {
+            extensions,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Possible errors that can be produced by [`RonAssetLoader`]
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum RonAssetLoaderError {
+    /// An [IO](std::io) Error
+    #[error("Could not load asset: {0}")]
+    Io(#[from] std::io::Error),
+    /// A [RON](ron) Error
+    #[error("Could not parse RON: {0}")]
+    RonSpannedError(#[from] ron::error::SpannedError),
+}
+
+impl<A> AssetLoader for RonAssetLoader<A>
+where
+    A: Asset + DeserializeOwned,
+{
+    type Asset = A;
+    type Settings = ();
+    type Error = RonAssetLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &Self::Settings,
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let asset = ron::de::from_bytes::<A>(&bytes)?;
+        Ok(asset)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
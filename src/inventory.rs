@@ -0,0 +1,69 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<Inventory>()
+        .register_type::<Inventory>();
+    }
+}
+
+/// Item id of the basic capture orb.
+pub const CAPTURE_ORB: &str = "capture_orb";
+
+/// Catch-rate multiplier of the basic capture orb.
+pub const CAPTURE_ORB_BONUS: f32 = 1.0;
+
+/// Orbs a new game starts with.
+const STARTING_ORBS: u32 = 5;
+
+/// Item counts keyed by item id.
+#[derive(Resource, Reflect, Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[reflect(Resource)]
+pub struct Inventory {
+    items: BTreeMap<String, u32>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        let mut inventory = Inventory {
+            items: BTreeMap::new(),
+        };
+        inventory.add(CAPTURE_ORB, STARTING_ORBS);
+        inventory
+    }
+}
+
+impl Inventory {
+    pub fn count(&self, item: &str) -> u32 {
+        self.items.get(item).copied().unwrap_or(0)
+    }
+
+    pub fn add(&mut self, item: &str, amount: u32) {
+        *self.items.entry(item.to_string()).or_default() += amount;
+    }
+
+    /// Uses up one of `item`, returning false if there was none.
+    pub fn take(&mut self, item: &str) -> bool {
+        match self.items.get_mut(item) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                if *count == 0 {
+                    self.items.remove(item);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.items.iter().map(|(item, count)| (item.as_str(), *count))
+    }
+}
@@ -11,8 +11,29 @@ mod helpers;
 mod mob;
 mod control;
 mod text_loading;
+mod text_layout;
+mod text_variables;
 mod dialog;
+mod speaker;
 mod state_stack;
+mod species;
+mod battle;
+mod rng;
+mod story_flags;
+mod trainer;
+mod encounter;
+mod party;
+mod inventory;
+mod storage;
+mod save;
+mod persist;
+mod options;
+mod pause;
+mod touch;
+mod replay;
+pub mod asset_check;
+#[cfg(test)]
+mod testing;
 
 use crate::audio::InternalAudioPlugin;
 use crate::loading::LoadingPlugin;
@@ -25,7 +46,27 @@ use crate::control::ControlPlugin;
 use crate::mob::MobPlugin;
 use crate::text_loading::TextLoadingPlugin;
 use crate::dialog::DialogPlugin;
+use crate::speaker::SpeakerPlugin;
+use crate::text_variables::TextVariablesPlugin;
 use crate::state_stack::StateStackPlugin;
+use crate::species::SpeciesPlugin;
+use crate::battle::BattlePlugin;
+use crate::display::battle::BattleDisplayPlugin;
+use crate::rng::RngPlugin;
+use crate::story_flags::StoryFlagsPlugin;
+use crate::trainer::TrainerPlugin;
+use crate::encounter::EncounterPlugin;
+use crate::party::PartyPlugin;
+use crate::inventory::InventoryPlugin;
+use crate::storage::StoragePlugin;
+use crate::display::storage::StorageDisplayPlugin;
+use crate::save::SavePlugin;
+use crate::options::OptionsPlugin;
+use crate::display::options::OptionsDisplayPlugin;
+use crate::pause::PausePlugin;
+use crate::replay::ReplayPlugin;
+
+pub use crate::touch::TouchControlsPlugin;
 
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 
@@ -57,6 +98,10 @@ enum GameState {
     // During this State the actual game logic is executed
     Playing,
     Dialog,
+    Battle,
+    Storage,
+    Options,
+    Pause,
     // Here the menu is drawn and waiting for player interaction
     Menu,
 }
@@ -91,11 +136,31 @@ impl Plugin for GamePlugin {
             MobDisplayPlugin,
             MapPlugin,
             ControlPlugin,
+            ReplayPlugin,
             TextLoadingPlugin,
             DialogPlugin,
+            SpeakerPlugin,
+            TextVariablesPlugin,
             StateStackPlugin,
             WorldInspectorPlugin::new(),
         ))
+        .add_plugins((
+            SpeciesPlugin,
+            RngPlugin,
+            BattlePlugin,
+            BattleDisplayPlugin,
+            StoryFlagsPlugin,
+            TrainerPlugin,
+            EncounterPlugin,
+            PartyPlugin,
+            InventoryPlugin,
+            StoragePlugin,
+            StorageDisplayPlugin,
+            SavePlugin,
+            OptionsPlugin,
+            OptionsDisplayPlugin,
+            PausePlugin,
+        ))
         .add_systems(Startup, (
             setup_camera, 
             // setup_sprite,
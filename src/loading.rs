@@ -3,6 +3,12 @@ use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
 use bevy_ecs_tiled::prelude::*;
 
+use crate::species::{Species, MoveList, SpeciesRegistry};
+use crate::battle::damage::TypeChart;
+use crate::trainer::TrainerList;
+use crate::encounter::EncounterTables;
+use crate::speaker::SpeakerList;
+
 pub struct LoadingPlugin;
 
 /// This plugin loads all assets using [`AssetLoader`] from a third party bevy plugin
@@ -18,6 +24,9 @@ impl Plugin for LoadingPlugin {
                 .load_collection::<TextureAssets>()
                 .load_collection::<MapAssets>()
                 .load_collection::<FontAssets>()
+                .load_collection::<DaemonAssets>()
+                .load_collection::<DialogAssets>()
+                .finally_init_resource::<SpeciesRegistry>()
         );
     }
 }
@@ -42,6 +51,39 @@ pub struct MapAssets {
 pub struct FontAssets {    
     #[asset(path = "fonts/Poco.ttf")]
     pub font: Handle<Font>,
+
+    #[asset(path = "fonts/PixeloidSans-Bold.ttf")]
+    pub bold_font: Handle<Font>,
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct DaemonAssets {
+    #[asset(paths(
+        "daemons/species/emberkit.species.ron",
+        "daemons/species/emberlord.species.ron",
+        "daemons/species/dewdrop.species.ron",
+        "daemons/species/mossling.species.ron",
+        "daemons/species/pebblit.species.ron",
+    ), collection(typed))]
+    pub species: Vec<Handle<Species>>,
+
+    #[asset(path = "daemons/moves.moves.ron")]
+    pub moves: Handle<MoveList>,
+
+    #[asset(path = "daemons/types.types.ron")]
+    pub types: Handle<TypeChart>,
+
+    #[asset(path = "daemons/trainers.trainers.ron")]
+    pub trainers: Handle<TrainerList>,
+
+    #[asset(path = "daemons/encounters.encounters.ron")]
+    pub encounters: Handle<EncounterTables>,
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct DialogAssets {
+    #[asset(path = "dialog/speakers.speakers.ron")]
+    pub speakers: Handle<SpeakerList>,
 }
 
 #[derive(AssetCollection, Resource)]
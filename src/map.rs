@@ -24,8 +24,8 @@ use crate::RES_WIDTH;
 use crate::RES_HEIGHT;
 use crate::PIXEL_PERFECT_STATIC_LAYERS;
 use crate::text_loading::GameText;
-use crate::text_loading::Dialog;
 use crate::dialog::CurrentDialog;
+use crate::trainer::Challenger;
 
 pub struct MapPlugin;
 
@@ -62,6 +62,7 @@ impl Plugin for MapPlugin {
         .init_resource::<GridIndex>()
         .register_type::<GridIndex>()
         .init_resource::<ChangeMapQueue>()
+        .init_resource::<SpawnOverride>()
         .init_resource::<MapChangedSinceMove>()
         .init_resource::<MapAndPlayerLoading>()
         .add_event::<PlayerSpawnEvent>()
@@ -94,6 +95,19 @@ pub struct SpawnData{
     pub from_direction: String,
 }
 
+/// The direction a [`SpawnData::from_direction`] names, or `None` if it
+/// isn't one. `center` is no direction at all.
+pub fn spawn_direction(name: &str) -> Option<GridTransform> {
+    match name {
+        "north" => Some(GridTransform::NORTH),
+        "east" => Some(GridTransform::EAST),
+        "south" => Some(GridTransform::SOUTH),
+        "west" => Some(GridTransform::WEST),
+        "center" => Some(GridTransform::ZERO),
+        _ => None,
+    }
+}
+
 #[derive(Component, Default, Debug, Reflect)]
 #[reflect(Component, Default)]
 #[require(IndexGridPosition)]
@@ -142,6 +156,11 @@ pub struct ChangeMapEvent {
 #[derive(Resource, Deref, DerefMut, Reflect, Debug, Default)]
 pub struct ChangeMapQueue(Vec<ChangeMapEvent>);
 
+/// Position and facing to put the player at on the next spawn instead of
+/// walking in from the spawn point, e.g. when loading a save.
+#[derive(Resource, Deref, DerefMut, Debug, Default)]
+pub struct SpawnOverride(pub Option<(GridTransform, GridTransform)>);
+
 #[derive(Resource, Deref, DerefMut, Reflect, Debug, Default)]
 pub struct MapChangedSinceMove(bool);
 
@@ -316,9 +335,13 @@ fn map_exits(
     for event in events.read() {
         if player_query.contains(event.moved) {
             if let Ok(exit) = exit_query.get(event.triggered) {
+                let Some(map) = map_assets.get_field::<Handle<TiledMap>>(&exit.map) else {
+                    error!("Exit leads to unknown map `{}`", exit.map);
+                    continue;
+                };
                 change_map_queue.push(
                     ChangeMapEvent{
-                        map: map_assets.get_field::<Handle<TiledMap>>(&exit.map).unwrap().clone(),
+                        map: map.clone(),
                         spawn: exit.spawn.to_string(),
                     }
                 );
@@ -328,7 +351,7 @@ fn map_exits(
 }
 
 fn trigger_dialog(
-    dialog_query: Query<&DialogReference>,
+    dialog_query: Query<&DialogReference, Without<Challenger>>,
     mut events: EventReader<TriggerEvent>,
     mut current_dialog: ResMut<CurrentDialog>,
     mut next_state: ResMut<NextState<GameState>>,
@@ -338,8 +361,8 @@ fn trigger_dialog(
     for event in events.read() {
         if let Ok(dialog) = dialog_query.get(event.triggered) {
             warn!("Dialog: {}", dialog.reference);
-            let next_dialog = game_text.get_field::<Dialog>(&dialog.reference).unwrap();
-            *current_dialog = CurrentDialog(Some(next_dialog.clone()));
+            let next_dialog = game_text.dialog_or_placeholder(&dialog.reference);
+            *current_dialog = CurrentDialog(Some(next_dialog));
             next_state.set(state_stack.push(GameState::Dialog));
         }
     }
@@ -394,7 +417,7 @@ pub struct ProcessedMap;
 #[derive(Component, Default)]
 pub struct TerrainMap;
 
-fn index_grid_positions(
+pub fn index_grid_positions(
     mut commands: Commands, 
     mut transform_query: Query<(Entity, &Transform), With<IndexGridPosition>>,
     mut tile_pos_query: Query<(Entity, &TilePos), With<IndexGridPosition>>,
@@ -477,14 +500,10 @@ fn mark_player_spawn(
             info!("Spawn point found");
             event.send(PlayerSpawnEvent{
                 location: (*transform).into(),
-                direction: match &data.from_direction {
-                    val if *val == "north".to_string() => GridTransform::NORTH,
-                    val if *val == "east".to_string()  => GridTransform::EAST,
-                    val if *val == "south".to_string()  => GridTransform::SOUTH,
-                    val if *val == "west".to_string()  => GridTransform::WEST,
-                    val if *val == "center".to_string()  => GridTransform::ZERO,
-                    _ => { panic!("invalid spawn direction") }
-                },
+                direction: spawn_direction(&data.from_direction).unwrap_or_else(|| {
+                    error!("Spawn `{}` has invalid direction `{}`", data.name, data.from_direction);
+                    GridTransform::ZERO
+                }),
             });
         }
     }
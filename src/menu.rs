@@ -16,6 +16,8 @@ use crate::map::ChangeMapQueue;
 use crate::loading::MapAssets;
 use crate::map::ChangeMapEvent;
 use crate::state_stack::StateStack;
+use crate::save::LoadGameEvent;
+use crate::save::SaveSlots;
 
 use bevy::text::TextLayoutInfo;
 
@@ -36,14 +38,18 @@ impl Plugin for MenuPlugin {
         .add_systems(Update, (
             update_menu_grid_index,
             update_cursor_transform.after(update_menu_grid_index),
+            menu_move_control,
+            menu_interact_control,
             trigger_exit,
             rounded_center_text,
         ))
         .add_systems(Update, (
-            menu_move_control,
-            menu_interact_control,
             trigger_game_start,
+            trigger_continue,
         ).run_if(in_state(GameState::Menu)))
+        .add_systems(Update, (
+            trigger_options,
+        ).run_if(in_state(GameState::Menu).or(in_state(GameState::Pause))))
         .init_resource::<MenuMovementCooldown>()
         .register_type::<MenuBox>()
         .register_type::<MenuElement>()
@@ -52,21 +58,23 @@ impl Plugin for MenuPlugin {
 }
 
 #[derive(Component, Default, Reflect)]
-struct MenuBox {
-    elements_index: HashMap<GridTransform, Entity>,
+pub struct MenuBox {
+    pub elements_index: HashMap<GridTransform, Entity>,
     pub grid_bounds: GridBounds,
 }
 
 #[derive(Component, Reflect)]
-struct MenuElement {
-    cursor_anchor: Transform,
-    menu_grid_position: GridTransform,
+pub struct MenuElement {
+    pub cursor_anchor: Transform,
+    pub menu_grid_position: GridTransform,
 }
 
+/// Cursor navigating the [`MenuBox`] it is focused on.
+/// Only visible cursors respond to controls, so several menus can exist at once.
 #[derive(Component, Reflect)]
-struct MenuCursor {
-     menu_focus: Entity,
-     menu_grid_position: GridTransform,
+pub struct MenuCursor {
+     pub menu_focus: Entity,
+     pub menu_grid_position: GridTransform,
 }
 
 #[derive(Default, Reflect, Clone, Copy)]
@@ -117,7 +125,12 @@ fn init_menu (
     fonts: Res<FontAssets>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    save_slots: Res<SaveSlots>,
+    mut state_stack: ResMut<StateStack>,
 ){
+    // The title screen is the bottom of the stack, so screens opened from it can return.
+    state_stack.push(GameState::Menu);
+
     // Basic background rect
     commands.spawn((
         Mesh2d(meshes.add(Rectangle::new(
@@ -179,6 +192,7 @@ fn init_menu (
         ("Options", Box::new(|cmd: &mut EntityCommands| {
             cmd.insert((
                 TriggerOnMenuInteract,
+                OptionsOnTriggered,
             ));
         })),
         ("Exit", Box::new(|cmd: &mut EntityCommands| {
@@ -189,6 +203,15 @@ fn init_menu (
         })),
     ];
 
+    if let Some(slot) = save_slots.latest() {
+        menu_items.insert(0, ("Continue", Box::new(move |cmd: &mut EntityCommands| {
+            cmd.insert((
+                TriggerOnMenuInteract,
+                ContinueOnTriggered(slot),
+            ));
+        })));
+    }
+
     // Spawn the menu box
     let menu_box_entity = commands
         .spawn((
@@ -401,7 +424,7 @@ fn menu_move_control(
     time: Res<Time>,
     mut cooldown: ResMut<MenuMovementCooldown>,
     mut control_events: EventReader<GameControlEvent>,
-    mut cursors: Query<&mut MenuCursor>,
+    mut cursors: Query<(&mut MenuCursor, &Visibility)>,
     menu_boxes: Query<&MenuBox>,
 ) {
     cooldown.tick(time.delta());
@@ -420,7 +443,8 @@ fn menu_move_control(
             _ => continue,
         };
 
-        for mut cursor in &mut cursors {
+        for (mut cursor, visibility) in &mut cursors {
+            if *visibility == Visibility::Hidden { continue }
             if let Ok(menu_box) = menu_boxes.get(cursor.menu_focus) {
                 let mut next_pos = cursor.menu_grid_position + movement;
 
@@ -453,7 +477,7 @@ fn menu_interact_control(
     mut control_events: EventReader<GameControlEvent>,
     mut trigger_event: EventWriter<TriggerEvent>,
     trigger_query: Query<Entity, With<TriggerOnMenuInteract>>,
-    cursors: Query<(Entity, &MenuCursor)>,
+    cursors: Query<(Entity, &MenuCursor, &Visibility)>,
     menu_boxes: Query<&MenuBox>,
 ) {
     match control_events.read()
@@ -462,7 +486,8 @@ fn menu_interact_control(
     .nth(0) {
         Some(_) => {
             if state.is_changed() { return }
-            for (cursor_entity, cursor) in &cursors {
+            for (cursor_entity, cursor, visibility) in &cursors {
+                if *visibility == Visibility::Hidden { continue }
                 if let Ok(menu_box) = menu_boxes.get(cursor.menu_focus) {
                     if let Some(element) = menu_box.elements_index.get(&cursor.menu_grid_position) {
                         if trigger_query.contains(element.clone()) {
@@ -526,6 +551,37 @@ fn trigger_game_start (
     }
 }
 
+/// Loads the save in the given slot.
+#[derive(Component)]
+pub struct ContinueOnTriggered(usize);
+
+fn trigger_continue (
+    continue_query: Query<&ContinueOnTriggered>,
+    mut events: EventReader<TriggerEvent>,
+    mut load_events: EventWriter<LoadGameEvent>,
+) {
+    for event in events.read() {
+        if let Ok(ContinueOnTriggered(slot)) = continue_query.get(event.triggered) {
+            load_events.send(LoadGameEvent { slot: *slot });
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct OptionsOnTriggered;
+
+fn trigger_options (
+    options_query: Query<(), With<OptionsOnTriggered>>,
+    mut events: EventReader<TriggerEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+) {
+    for event in events.read() {
+        if options_query.contains(event.triggered) {
+            next_state.set(state_stack.push(GameState::Options));
+        }
+    }
+}
 
 fn compute_text_bounds(text_layout: &TextLayoutInfo) -> Vec2 {
     let mut max_x: f32 = 0.0;
@@ -99,10 +99,24 @@ impl Default for SightTriggerCooldown {
 #[derive(Component, Deref, DerefMut, Reflect, Debug)]
 pub struct MovementCooldown(Timer);
 
+impl MovementCooldown {
+    /// How long a step takes when walking.
+    pub const WALK: Duration = Duration::from_millis(400);
+
+    /// Changes how long the next steps take. Ignored mid-step, so a step
+    /// never jumps ahead or back.
+    pub fn set_step_duration(&mut self, duration: Duration) {
+        if self.finished() && self.duration() != duration {
+            self.set_duration(duration);
+            self.set_elapsed(duration);
+        }
+    }
+}
+
 impl Default for MovementCooldown {
     fn default() -> Self {
         let mut timer = Timer::new(
-            Duration::from_secs_f32(0.4),
+            MovementCooldown::WALK,
             TimerMode::Once
         );
         timer.finish();
@@ -135,6 +149,7 @@ impl Plugin for MobPlugin {
         ).run_if(in_state(GameState::Playing)))
         .add_event::<TriggerOnMoveOntoEvent>()
         .add_event::<MobMoveEvent>()
+        .add_event::<MobSteppedEvent>()
         .add_event::<MobInteractEvent>()
         .register_type::<Mob>()
         .register_type::<AnimationIndex>()
@@ -159,6 +174,12 @@ pub struct MobMoveEvent  {
     pub movement: GridTransform,
 }
 
+/// A mob finished a [`MobMoveEvent`] onto a new tile.
+#[derive(Event, Reflect, Debug)]
+pub struct MobSteppedEvent {
+    pub entity: Entity,
+}
+
 fn init_grid_from_transform(
     mut commands: Commands,    
     mut query: Query<(
@@ -189,6 +210,7 @@ fn move_mob(
     mut grid_index: ResMut<GridIndex>,
     mut mob_move_events: EventReader<MobMoveEvent>,
     mut move_trigger_event: EventWriter<TriggerOnMoveOntoEvent>,
+    mut stepped_events: EventWriter<MobSteppedEvent>,
 ) {
     for event in mob_move_events.read() {
         if let Ok((mob_entity, mut pos, mut last_pos, mut dir, mut cooldown)) = query.get_mut(event.entity) {
@@ -211,6 +233,7 @@ fn move_mob(
                     }
 
                     grid_index.update(mob_entity, new_pos);
+                    stepped_events.send(MobSteppedEvent { entity: mob_entity });
                 }
             }
         }
@@ -265,9 +288,36 @@ fn mob_interact(
 #[require(SightTriggerCooldown)]
 struct TriggerOnSeePlayer;
 
+/// Stops trainers and other [`TriggerOnSeePlayer`] mobs seeing past it.
 #[derive(Component, Default, Debug, Reflect)]
 #[reflect(Component, Default)]
-struct BlocksSight;
+pub struct BlocksSight;
+
+/// Tiles a mob can see ahead of it.
+const SIGHT_RANGE: i16 = 16;
+
+/// The player seen looking from `position` in `direction`, unless something
+/// blocks sight before them.
+pub fn player_in_sight(
+    position: GridTransform,
+    direction: GridTransform,
+    grid_index: &GridIndex,
+    player_query: &Query<Entity, With<Player>>,
+    blocks_sight_query: &Query<(), With<BlocksSight>>,
+) -> Option<Entity> {
+    for step in 1..=SIGHT_RANGE {
+        let occupants = grid_index.get(&GridPosition(position + direction.mult(step)));
+        for &occupant in occupants {
+            if blocks_sight_query.contains(occupant) {
+                return None;
+            }
+            if player_query.contains(occupant) {
+                return Some(occupant);
+            }
+        }
+    }
+    None
+}
 
 fn trigger_on_see_player(
     time: Res<Time>,
@@ -285,40 +335,69 @@ fn trigger_on_see_player(
     for (triggered, grid_pos, grid_dir, mut cooldown) in &mut trigger_query {
         (**cooldown).tick(time.delta());
         if !cooldown.finished() {
-            break;
+            continue;
         }
-        for step in 1..=16 {
-            let check_pos = **grid_pos + (**grid_dir).mult(step);
-            let occupants = grid_index.get(&GridPosition(check_pos));
-
-            // Check each occupant for blocking or a player
-            let mut blocked = false;
-            let mut found_player = false;
-
-            for &occupant in occupants {
-                // If this occupant blocks sight, stop scanning entirely.
-                if blocks_sight_query.contains(occupant) {
-                    blocked = true;
-                    break;
-                }
+        let seen = player_in_sight(**grid_pos, **grid_dir, &grid_index, &player_query, &blocks_sight_query);
+        if let Some(player) = seen {
+            cooldown.reset();
+            trigger_events.send(TriggerEvent {
+                triggering: player,
+                triggered,
+            });
+        }
+    }
+}
 
-                // If this occupant is a player, we send the event and stop.
-                if player_query.contains(occupant) {
-                    warn!("PLAYER DETECTED");
-                    cooldown.reset();
-                    trigger_events.send(TriggerEvent {
-                        triggering: occupant,
-                        triggered: triggered,
-                    });
-                    found_player = true;
-                    break;
-                }
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::GameControl;
+    use crate::testing::TestApp;
+    use crate::testing::STEP_TICKS;
+
+    #[test]
+    fn walking_moves_one_tile_per_step() {
+        let mut app = TestApp::new();
+        let player = app.spawn_player(GridTransform::ZERO);
+
+        app.hold(GameControl::Right, STEP_TICKS);
+        assert_eq!(app.position(player), GridTransform::EAST);
+        app.hold(GameControl::Up, STEP_TICKS);
+        assert_eq!(app.position(player), GridTransform::NORTH_EAST);
+    }
 
-            // If vision is blocked or we've found a player, stop scanning further.
-            if blocked || found_player {
-                break;
-            }
-        }
+    #[test]
+    fn blocked_tiles_stop_movement() {
+        let mut app = TestApp::new();
+        let player = app.spawn_player(GridTransform::ZERO);
+        app.spawn_at(GridTransform::NORTH, BlocksWalking);
+        app.tick(1);
+
+        app.hold(GameControl::Up, STEP_TICKS * 2);
+        assert_eq!(app.position(player), GridTransform::ZERO);
+        // Still turns to face the wall.
+        assert_eq!(app.app.world().get::<GridDirection>(player).unwrap().0, GridTransform::NORTH);
+    }
+
+    #[test]
+    fn moving_onto_a_trigger_fires_it() {
+        let mut app = TestApp::new();
+        let player = app.spawn_player(GridTransform::ZERO);
+        let trigger = app.spawn_at(GridTransform::SOUTH, TriggerOnMoveOnto);
+
+        app.hold(GameControl::Down, STEP_TICKS);
+        assert_eq!(app.triggers().moved_onto, vec![(player, trigger)]);
+    }
+
+    #[test]
+    fn interacting_fires_the_faced_trigger() {
+        let mut app = TestApp::new();
+        let player = app.spawn_player(GridTransform::ZERO);
+        let sign = app.spawn_at(GridTransform::SOUTH, (TriggerOnInteract, BlocksWalking));
+        app.tick(1);
+
+        app.press(GameControl::Interact);
+        app.tick(1);
+        assert_eq!(app.triggers().triggered, vec![(player, sign)]);
     }
 }
@@ -0,0 +1,155 @@
+use bevy::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::control::key_label;
+use crate::control::map_inputs_to_control_events;
+use crate::control::GameControl;
+use crate::control::InputMap;
+use crate::mob::TriggerEvent;
+use crate::persist::Persistence;
+use crate::replay::is_replaying;
+use crate::state_stack::StateStack;
+use crate::text_loading::Language;
+use crate::GameState;
+
+pub struct OptionsPlugin;
+
+impl Plugin for OptionsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(OnEnter(GameState::Options), (
+            reset_options_view,
+        ))
+        .add_systems(Update, (
+            listen_for_rebind_key
+                .before(map_inputs_to_control_events)
+                .run_if(not(is_replaying)),
+            capture_rebind_key.after(listen_for_rebind_key),
+            options_menu_control,
+        ).run_if(in_state(GameState::Options)))
+        .init_resource::<OptionsView>()
+        .add_event::<RebindKeyEvent>();
+    }
+}
+
+/// Keys that can be bound to each control from the options screen.
+pub const KEY_SLOTS: usize = 2;
+
+/// A key slot of a control on the options screen.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyBindingSlot {
+    pub control: GameControl,
+    pub index: usize,
+}
+
+/// Menu elements in the options screen that aren't key slots.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionsMenuAction {
+    /// Switches to the next of [`crate::text_loading::LANGUAGES`].
+    Language,
+    ResetDefaults,
+    Back,
+}
+
+/// A key pressed while a slot is listening. Sent apart from the controls so
+/// recordings can play rebinds back.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RebindKeyEvent(pub KeyCode);
+
+/// State of the options screen while it is open.
+#[derive(Resource, Default, Debug)]
+pub struct OptionsView {
+    /// Slot waiting for the next key press.
+    pub listening: Option<KeyBindingSlot>,
+    /// Feedback shown at the bottom of the screen.
+    pub message: String,
+}
+
+fn reset_options_view(
+    mut view: ResMut<OptionsView>,
+) {
+    view.listening = None;
+    view.message = "Pick a key to change.".to_string();
+}
+
+fn options_menu_control(
+    mut events: EventReader<TriggerEvent>,
+    slot_query: Query<&KeyBindingSlot>,
+    action_query: Query<&OptionsMenuAction>,
+    mut view: ResMut<OptionsView>,
+    mut input_map: ResMut<InputMap>,
+    mut language: ResMut<Language>,
+    mut persistence: ResMut<Persistence>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+) {
+    for event in events.read() {
+        if view.listening.is_some() { continue }
+        if let Ok(slot) = slot_query.get(event.triggered) {
+            view.listening = Some(*slot);
+            view.message = format!("Press a key for {}.", slot.control.label());
+            continue;
+        }
+        match action_query.get(event.triggered) {
+            Ok(OptionsMenuAction::Language) => {
+                *language = language.next();
+                language.save(&mut persistence);
+                view.message = format!("Language set to {}.", language.name());
+            }
+            Ok(OptionsMenuAction::ResetDefaults) => {
+                *input_map = InputMap::defaults();
+                input_map.save(&mut persistence);
+                view.message = "Controls reset.".to_string();
+            }
+            Ok(OptionsMenuAction::Back) => {
+                next_state.set(state_stack.back());
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// Sends the next key pressed while a slot is listening.
+///
+/// The key is consumed so it doesn't also act as a control this frame.
+fn listen_for_rebind_key(
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    view: Res<OptionsView>,
+    mut rebind_events: EventWriter<RebindKeyEvent>,
+) {
+    if view.listening.is_none() { return }
+    let Some(key) = keys.get_just_pressed().next().copied() else { return };
+    keys.clear_just_pressed(key);
+    rebind_events.send(RebindKeyEvent(key));
+}
+
+/// Binds the key to the slot being listened to. Escape cancels.
+pub fn capture_rebind_key(
+    mut rebind_events: EventReader<RebindKeyEvent>,
+    mut view: ResMut<OptionsView>,
+    mut input_map: ResMut<InputMap>,
+    mut persistence: ResMut<Persistence>,
+) {
+    for RebindKeyEvent(key) in rebind_events.read().copied() {
+        let Some(slot) = view.listening else { return };
+        view.listening = None;
+
+        if key == KeyCode::Escape {
+            view.message = "Pick a key to change.".to_string();
+            continue;
+        }
+        let replacing = input_map.keys_for(slot.control).get(slot.index).copied();
+        view.message = match input_map.rebind(slot.control, replacing, key) {
+            Ok(()) => {
+                input_map.save(&mut persistence);
+                format!("{} set to {}.", slot.control.label(), key_label(key))
+            }
+            Err(conflict) => format!(
+                "{} is used for {}.",
+                key_label(key),
+                conflict.bound_to.label(),
+            ),
+        };
+    }
+}
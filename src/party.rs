@@ -0,0 +1,509 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::battle::engine::Combatant;
+use crate::battle::BattleEndEvent;
+use crate::battle::engine::MoveSlot;
+use crate::battle::engine::Stats;
+use crate::battle::engine::MAX_MOVES;
+use crate::dialog::DialogOutcomeEvent;
+use crate::rng::GameRng;
+use crate::species::EvolutionCondition;
+use crate::species::MoveData;
+use crate::species::Species;
+use crate::species::SpeciesRegistry;
+use crate::GameState;
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+pub struct PartyPlugin;
+
+impl Plugin for PartyPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(OnEnter(GameState::Playing), (
+            grant_starter,
+        ))
+        .add_systems(Update, (
+            evolve_after_battle,
+            heal_on_dialog_outcome,
+        ))
+        .init_resource::<Party>()
+        .register_type::<Party>()
+        .register_type::<DaemonInstance>()
+        .add_event::<LevelUpEvent>();
+    }
+}
+
+/// Most daemons the player can carry at once.
+pub const PARTY_SIZE: usize = 6;
+
+/// Highest level a daemon can reach.
+pub const MAX_LEVEL: u8 = 100;
+
+/// Dialog outcome that restores the whole party.
+pub const HEAL_PARTY_OUTCOME: &str = "heal_party";
+
+/// Highest individual value for a single stat.
+pub const MAX_IV: u16 = 31;
+
+/// Species and level every new game starts with.
+const STARTER: (&str, u8) = ("emberkit", 5);
+
+#[derive(Reflect, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StatusCondition {
+    #[default]
+    Healthy,
+    Poisoned,
+    Burned,
+    Paralyzed,
+    Asleep,
+    Frozen,
+}
+
+/// Per-stat bonuses: individual values are rolled once when a daemon is
+/// created, effort values grow with the battles it fights.
+#[derive(Reflect, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatSpread {
+    pub hp: u16,
+    pub attack: u16,
+    pub defense: u16,
+    pub sp_attack: u16,
+    pub sp_defense: u16,
+    pub speed: u16,
+}
+
+impl StatSpread {
+    pub fn random_ivs(rng: &mut impl Rng) -> Self {
+        let mut roll = || rng.gen_range(0..=MAX_IV);
+        StatSpread {
+            hp: roll(),
+            attack: roll(),
+            defense: roll(),
+            sp_attack: roll(),
+            sp_defense: roll(),
+            speed: roll(),
+        }
+    }
+}
+
+/// How much experience a species needs to reach each level.
+#[derive(Reflect, Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GrowthRate {
+    Fast,
+    #[default]
+    MediumFast,
+    MediumSlow,
+    Slow,
+}
+
+impl GrowthRate {
+    /// Total experience needed to be at `level`.
+    pub fn xp_for_level(self, level: u8) -> u32 {
+        let n = level.clamp(1, MAX_LEVEL) as i64;
+        if n == 1 {
+            return 0;
+        }
+        let xp = match self {
+            GrowthRate::Fast => 4 * n.pow(3) / 5,
+            GrowthRate::MediumFast => n.pow(3),
+            GrowthRate::MediumSlow => 6 * n.pow(3) / 5 - 15 * n.pow(2) + 100 * n - 140,
+            GrowthRate::Slow => 5 * n.pow(3) / 4,
+        };
+        xp.max(0) as u32
+    }
+
+    /// Level reached with `xp` total experience.
+    pub fn level_for_xp(self, xp: u32) -> u8 {
+        (1..MAX_LEVEL)
+            .find(|level| self.xp_for_level(level + 1) > xp)
+            .unwrap_or(MAX_LEVEL)
+    }
+}
+
+/// What happened when a daemon tried to learn a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LearnResult {
+    Learned,
+    AlreadyKnown,
+    /// Every slot is taken; the player has to choose a move to forget.
+    SlotsFull,
+}
+
+/// A single daemon owned by the player.
+#[derive(Reflect, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct DaemonInstance {
+    pub species: String,
+    pub nickname: Option<String>,
+    pub level: u8,
+    /// Total experience, see [`GrowthRate::xp_for_level`].
+    pub xp: u32,
+    pub hp: u16,
+    pub ivs: StatSpread,
+    pub evs: StatSpread,
+    pub moves: Vec<MoveSlot>,
+    pub status: StatusCondition,
+    /// Order in which the daemon was caught, see [`crate::storage::DaemonStorage::record_capture`].
+    #[serde(default)]
+    pub caught_order: u32,
+}
+
+impl DaemonInstance {
+    /// A fresh daemon at full health knowing the last moves of its learnset up to `level`.
+    pub fn new(
+        species: &Species,
+        level: u8,
+        moves: &HashMap<String, MoveData>,
+        rng: &mut impl Rng,
+    ) -> Self {
+        let level = level.clamp(1, MAX_LEVEL);
+        let known: Vec<&str> = species.learnset.iter()
+            .filter(|entry| entry.level <= level)
+            .map(|entry| entry.move_id.as_str())
+            .collect();
+        let moves = known.iter()
+            .skip(known.len().saturating_sub(MAX_MOVES))
+            .filter_map(|id| moves.get(*id).map(|data| MoveSlot {
+                id: id.to_string(),
+                pp: data.pp,
+            }))
+            .collect();
+
+        let mut daemon = DaemonInstance {
+            species: species.id.clone(),
+            nickname: None,
+            level,
+            xp: species.growth_rate.xp_for_level(level),
+            hp: 0,
+            ivs: StatSpread::random_ivs(rng),
+            evs: StatSpread::default(),
+            moves,
+            status: StatusCondition::Healthy,
+            caught_order: 0,
+        };
+        daemon.hp = daemon.stats(species).hp;
+        daemon
+    }
+
+    pub fn name<'a>(&'a self, species: &'a Species) -> &'a str {
+        self.nickname.as_deref().unwrap_or(&species.name)
+    }
+
+    pub fn stats(&self, species: &Species) -> Stats {
+        Stats::calculate(&species.base_stats, &self.ivs, &self.evs, self.level)
+    }
+
+    pub fn is_fainted(&self) -> bool {
+        self.hp == 0
+    }
+
+    /// Restores HP, PP and status.
+    pub fn heal(&mut self, species: &Species, moves: &HashMap<String, MoveData>) {
+        self.hp = self.stats(species).hp;
+        self.status = StatusCondition::Healthy;
+        for slot in &mut self.moves {
+            if let Some(data) = moves.get(&slot.id) {
+                slot.pp = data.pp;
+            }
+        }
+    }
+
+    /// Takes the HP, status and PP `combatant` has left. PP is matched by
+    /// move id, so moves learned during the battle are kept.
+    pub fn sync_with(&mut self, combatant: &Combatant) {
+        self.hp = combatant.hp;
+        self.status = combatant.status;
+        for slot in &mut self.moves {
+            if let Some(used) = combatant.moves.iter().find(|used| used.id == slot.id) {
+                slot.pp = used.pp;
+            }
+        }
+    }
+
+    /// Adds experience and returns each level gained, in order.
+    ///
+    /// HP grows by as much as max HP does, so a level-up never hurts.
+    pub fn gain_xp(&mut self, amount: u32, species: &Species) -> Vec<u8> {
+        let old_max_hp = self.stats(species).hp;
+        self.xp = self.xp
+            .saturating_add(amount)
+            .min(species.growth_rate.xp_for_level(MAX_LEVEL));
+        let new_level = species.growth_rate.level_for_xp(self.xp);
+        let gained: Vec<u8> = (self.level + 1..=new_level).collect();
+        self.level = new_level.max(self.level);
+
+        self.grow_hp(old_max_hp, self.stats(species).hp);
+        gained
+    }
+
+    /// Turns the daemon into the species it evolves into. HP grows with max
+    /// HP the same way as on a level-up.
+    pub fn evolve(&mut self, from: &Species, into: &Species) {
+        let old_max_hp = self.stats(from).hp;
+        self.species = into.id.clone();
+        self.grow_hp(old_max_hp, self.stats(into).hp);
+    }
+
+    fn grow_hp(&mut self, old_max_hp: u16, new_max_hp: u16) {
+        if !self.is_fainted() {
+            self.hp = (self.hp + new_max_hp.saturating_sub(old_max_hp)).min(new_max_hp);
+        }
+    }
+
+    /// Moves from the learnset that become available at exactly `level`.
+    pub fn moves_learned_at(species: &Species, level: u8) -> impl Iterator<Item = &str> {
+        species.learnset.iter()
+            .filter(move |entry| entry.level == level)
+            .map(|entry| entry.move_id.as_str())
+    }
+
+    /// Teaches a move if there is a free slot.
+    pub fn learn_move(&mut self, move_id: &str, data: &MoveData) -> LearnResult {
+        if self.moves.iter().any(|slot| slot.id == move_id) {
+            return LearnResult::AlreadyKnown;
+        }
+        if self.moves.len() >= MAX_MOVES {
+            return LearnResult::SlotsFull;
+        }
+        self.moves.push(MoveSlot {
+            id: move_id.to_string(),
+            pp: data.pp,
+        });
+        LearnResult::Learned
+    }
+}
+
+/// Experience awarded for defeating a daemon of `species` at `level`.
+pub fn xp_reward(species: &Species, level: u8, trainer_owned: bool) -> u32 {
+    let base = species.xp_yield as u32 * level as u32 / 7;
+    let base = if trainer_owned { base * 3 / 2 } else { base };
+    base.max(1)
+}
+
+/// The daemons travelling with the player, in battle order.
+#[derive(Resource, Reflect, Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[reflect(Resource)]
+pub struct Party {
+    members: Vec<DaemonInstance>,
+}
+
+impl Party {
+    pub fn members(&self) -> &[DaemonInstance] {
+        &self.members
+    }
+
+    pub fn members_mut(&mut self) -> &mut [DaemonInstance] {
+        &mut self.members
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut DaemonInstance> {
+        self.members.get_mut(index)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.members.len() >= PARTY_SIZE
+    }
+
+    pub fn has_healthy_member(&self) -> bool {
+        self.members.iter().any(|daemon| !daemon.is_fainted())
+    }
+
+    /// Adds a daemon to the end of the party, handing it back if there is no room.
+    pub fn add(&mut self, daemon: DaemonInstance) -> Result<(), DaemonInstance> {
+        if self.is_full() {
+            return Err(daemon);
+        }
+        self.members.push(daemon);
+        Ok(())
+    }
+
+    /// Swaps the daemon at `index` for another, returning the old one.
+    pub fn replace(&mut self, index: usize, daemon: DaemonInstance) -> DaemonInstance {
+        std::mem::replace(&mut self.members[index], daemon)
+    }
+
+    pub fn remove(&mut self, index: usize) -> DaemonInstance {
+        self.members.remove(index)
+    }
+
+    pub fn insert(&mut self, index: usize, daemon: DaemonInstance) {
+        self.members.insert(index, daemon);
+    }
+
+    pub fn heal_all(&mut self, registry: &SpeciesRegistry) {
+        for daemon in &mut self.members {
+            if let Some(species) = registry.species.get(&daemon.species) {
+                daemon.heal(species, &registry.moves);
+            }
+        }
+    }
+}
+
+#[derive(Event, Debug)]
+pub struct LevelUpEvent {
+    pub index: usize,
+    pub level: u8,
+}
+
+fn heal_on_dialog_outcome(
+    mut events: EventReader<DialogOutcomeEvent>,
+    mut party: ResMut<Party>,
+    registry: Res<SpeciesRegistry>,
+) {
+    for event in events.read() {
+        if event.outcome == HEAL_PARTY_OUTCOME {
+            party.heal_all(&registry);
+        }
+    }
+}
+
+/// A new game starts with an empty party; hand out the starter.
+fn grant_starter(
+    mut party: ResMut<Party>,
+    registry: Res<SpeciesRegistry>,
+    mut rng: ResMut<GameRng>,
+) {
+    if !party.is_empty() {
+        return;
+    }
+    let (species_id, level) = STARTER;
+    match registry.species.get(species_id) {
+        Some(species) => {
+            let _ = party.add(DaemonInstance::new(species, level, &registry.moves, &mut **rng));
+        }
+        None => error!("Starter species `{}` is not loaded", species_id),
+    }
+}
+
+/// Evolves party members whose species has a level evolution they now meet.
+/// Waits for the battle they levelled up in to end, so the battle never sees
+/// the species change under it.
+fn evolve_after_battle(
+    mut level_up_events: EventReader<LevelUpEvent>,
+    mut battle_end_events: EventReader<BattleEndEvent>,
+    mut levelled_up: Local<BTreeMap<usize, u8>>,
+    mut party: ResMut<Party>,
+    registry: Res<SpeciesRegistry>,
+) {
+    for event in level_up_events.read() {
+        let level = levelled_up.entry(event.index).or_default();
+        *level = event.level.max(*level);
+    }
+    if battle_end_events.read().count() == 0 {
+        return;
+    }
+    for (index, level) in std::mem::take(&mut *levelled_up) {
+        let Some(daemon) = party.get_mut(index) else {
+            continue;
+        };
+        let Some(species) = registry.species.get(&daemon.species) else {
+            continue;
+        };
+        let evolution = species.evolutions.iter().find(|evolution| match evolution.condition {
+            EvolutionCondition::Level(evolution_level) => level >= evolution_level,
+        });
+        let Some(into) = evolution.and_then(|evolution| registry.species.get(&evolution.into)) else {
+            continue;
+        };
+        info!("{} evolved into {}", daemon.name(species), into.name);
+        daemon.evolve(species, into);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use crate::species::MoveList;
+
+    fn emberkit() -> Species {
+        ron::de::from_str(include_str!("../assets/daemons/species/emberkit.species.ron")).unwrap()
+    }
+
+    fn moves() -> MoveList {
+        ron::de::from_str(include_str!("../assets/daemons/moves.moves.ron")).unwrap()
+    }
+
+    #[test]
+    fn moves_learned_in_battle_survive_syncing() {
+        let (species, moves) = (emberkit(), moves());
+        let mut daemon = DaemonInstance::new(&species, 8, &moves, &mut StdRng::seed_from_u64(7));
+        let mut combatant = Combatant::from_instance(&daemon, &species);
+        combatant.moves[0].pp -= 3;
+        combatant.hp -= 5;
+
+        assert_eq!(daemon.learn_move("quick_strike", &moves["quick_strike"]), LearnResult::Learned);
+        daemon.sync_with(&combatant);
+        let ids: Vec<&str> = daemon.moves.iter().map(|slot| slot.id.as_str()).collect();
+        assert_eq!(ids, ["scratch", "ember", "quick_strike"]);
+        assert_eq!(daemon.moves[0].pp, moves["scratch"].pp - 3);
+        assert_eq!(daemon.hp, combatant.hp);
+    }
+
+    #[test]
+    fn xp_curves_round_trip() {
+        for rate in [GrowthRate::Fast, GrowthRate::MediumFast, GrowthRate::MediumSlow, GrowthRate::Slow] {
+            assert_eq!(rate.xp_for_level(1), 0);
+            for level in 2..=MAX_LEVEL {
+                let xp = rate.xp_for_level(level);
+                assert_eq!(rate.level_for_xp(xp), level, "{:?} at {}", rate, level);
+                assert_eq!(rate.level_for_xp(xp - 1), level - 1, "{:?} below {}", rate, level);
+            }
+        }
+        assert_eq!(GrowthRate::MediumFast.xp_for_level(10), 1000);
+    }
+
+    #[test]
+    fn gaining_xp_levels_up() {
+        let species = emberkit();
+        let mut daemon = DaemonInstance::new(&species, 5, &moves(), &mut StdRng::seed_from_u64(7));
+        let max_hp = daemon.stats(&species).hp;
+        daemon.hp = max_hp - 5;
+
+        let to_level_8 = species.growth_rate.xp_for_level(8) - daemon.xp;
+        assert_eq!(daemon.gain_xp(to_level_8, &species), [6, 7, 8]);
+        assert_eq!(daemon.level, 8);
+        assert_eq!(daemon.hp, daemon.stats(&species).hp - 5);
+
+        daemon.hp = 0;
+        assert_eq!(daemon.gain_xp(u32::MAX, &species).len(), (MAX_LEVEL - 8) as usize);
+        assert_eq!(daemon.level, MAX_LEVEL);
+        assert_eq!(daemon.xp, species.growth_rate.xp_for_level(MAX_LEVEL));
+        assert_eq!(daemon.hp, 0);
+        assert!(daemon.gain_xp(1000, &species).is_empty());
+    }
+
+    #[test]
+    fn learning_moves() {
+        let (species, moves) = (emberkit(), moves());
+        let mut daemon = DaemonInstance::new(&species, 14, &moves, &mut StdRng::seed_from_u64(7));
+        assert_eq!(daemon.moves.len(), MAX_MOVES);
+        assert_eq!(daemon.learn_move("ember", &moves["ember"]), LearnResult::AlreadyKnown);
+        assert_eq!(daemon.learn_move("water_gun", &moves["water_gun"]), LearnResult::SlotsFull);
+
+        daemon.moves.pop();
+        assert_eq!(daemon.learn_move("water_gun", &moves["water_gun"]), LearnResult::Learned);
+        assert_eq!(daemon.moves[MAX_MOVES - 1], MoveSlot { id: "water_gun".to_string(), pp: moves["water_gun"].pp });
+        assert_eq!(DaemonInstance::moves_learned_at(&species, 9).collect::<Vec<_>>(), ["quick_strike"]);
+    }
+
+    #[test]
+    fn evolving_keeps_damage_taken() {
+        let (emberkit, moves) = (emberkit(), moves());
+        let emberlord: Species = ron::de::from_str(include_str!("../assets/daemons/species/emberlord.species.ron")).unwrap();
+        let mut daemon = DaemonInstance::new(&emberkit, 16, &moves, &mut StdRng::seed_from_u64(7));
+        daemon.hp -= 10;
+
+        daemon.evolve(&emberkit, &emberlord);
+        assert_eq!(daemon.species, "emberlord");
+        assert_eq!(daemon.hp, daemon.stats(&emberlord).hp - 10);
+    }
+}
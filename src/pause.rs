@@ -0,0 +1,190 @@
+use bevy::ecs::system::EntityCommands;
+use bevy::prelude::*;
+use bevy::sprite::*;
+
+use crate::control::GameControl;
+use crate::control::GameControlEvent;
+use crate::graph::grid_transform::GridTransform;
+use crate::loading::FontAssets;
+use crate::loading::TextureAssets;
+use crate::menu::ExitOnTriggered;
+use crate::menu::MenuBox;
+use crate::menu::MenuCursor;
+use crate::menu::MenuElement;
+use crate::menu::OptionsOnTriggered;
+use crate::menu::TriggerOnMenuInteract;
+use crate::mob::TriggerEvent;
+use crate::state_stack::StateStack;
+use crate::trainer::TrainerEncounter;
+use crate::GameState;
+use crate::PIXEL_PERFECT_STATIC_LAYERS;
+use crate::RES_HEIGHT;
+use crate::RES_WIDTH;
+
+pub struct PausePlugin;
+
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(OnExit(GameState::AssetLoading), (
+            init_pause_menu,
+        ))
+        .add_systems(OnEnter(GameState::Pause), (
+            enter_pause_menu,
+        ))
+        .add_systems(OnExit(GameState::Pause), (
+            exit_pause_menu,
+        ))
+        .add_systems(Update, (
+            open_pause_menu,
+        ).run_if(in_state(GameState::Playing)))
+        .add_systems(Update, (
+            close_pause_menu,
+            trigger_resume,
+        ).run_if(in_state(GameState::Pause)));
+    }
+}
+
+const TEXT_COLOR: Color = Color::srgb(47. / 255., 76. / 255., 64. / 255.);
+const MENU_SIZE: Vec2 = Vec2::new(56., 50.);
+
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Component)]
+pub struct ResumeOnTriggered;
+
+fn init_pause_menu(
+    mut commands: Commands,
+    textures: Res<TextureAssets>,
+    fonts: Res<FontAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+) {
+    let text_font = TextFont {
+        font: fonts.font.clone(),
+        font_size: 10.,
+        ..Default::default()
+    };
+
+    let origin = Vec2::new(RES_WIDTH as f32, RES_HEIGHT as f32) - MENU_SIZE;
+
+    commands.spawn((
+        Mesh2d(meshes.add(Rectangle::from_size(MENU_SIZE))),
+        MeshMaterial2d(materials.add(Color::srgb_u8(224, 240, 232))),
+        Transform::from_translation((origin + MENU_SIZE / 2.).extend(20.)),
+        Visibility::Hidden,
+        PIXEL_PERFECT_STATIC_LAYERS,
+        PauseMenu,
+    ));
+
+    let menu_items: [(&str, fn(&mut EntityCommands)); 3] = [
+        ("Resume", |cmd| { cmd.insert(ResumeOnTriggered); }),
+        ("Options", |cmd| { cmd.insert(OptionsOnTriggered); }),
+        ("Exit", |cmd| { cmd.insert(ExitOnTriggered); }),
+    ];
+
+    let menu_box = commands.spawn((
+        Transform::from_xyz(origin.x + 10., RES_HEIGHT as f32 - 16., 21.),
+        Visibility::Hidden,
+        PIXEL_PERFECT_STATIC_LAYERS,
+        MenuBox::default(),
+        PauseMenu,
+    )).with_children(|builder| {
+        for (index, (label, add_action)) in menu_items.into_iter().enumerate() {
+            let mut entity = builder.spawn((
+                Text2d::new(label.to_string()),
+                text_font.clone(),
+                TextColor(TEXT_COLOR),
+                Anchor::BottomLeft,
+                Transform::from_xyz(0., -(index as f32) * 14., 1.),
+                PIXEL_PERFECT_STATIC_LAYERS,
+                MenuElement {
+                    cursor_anchor: Transform::from_xyz(-5., 1., 1.),
+                    menu_grid_position: GridTransform::new(0, -(index as i16)),
+                },
+                TriggerOnMenuInteract,
+            ));
+            add_action(&mut entity);
+        }
+    }).id();
+
+    commands.spawn((
+        Transform::from_xyz(0., 0., 30.),
+        Sprite {
+            image: textures.menu_pointer.clone(),
+            anchor: Anchor::BottomLeft,
+            ..default()
+        },
+        Visibility::Hidden,
+        MenuCursor {
+            menu_focus: menu_box,
+            menu_grid_position: GridTransform::ZERO,
+        },
+        PIXEL_PERFECT_STATIC_LAYERS,
+        PauseMenu,
+    ));
+}
+
+fn enter_pause_menu(
+    mut pause_menu_query: Query<&mut Visibility, With<PauseMenu>>,
+    mut cursor_query: Query<&mut MenuCursor, With<PauseMenu>>,
+) {
+    for mut visibility in &mut pause_menu_query {
+        *visibility = Visibility::Inherited;
+    }
+    for mut cursor in &mut cursor_query {
+        cursor.menu_grid_position = GridTransform::ZERO;
+    }
+}
+
+fn exit_pause_menu(
+    mut pause_menu_query: Query<&mut Visibility, With<PauseMenu>>,
+) {
+    for mut visibility in &mut pause_menu_query {
+        *visibility = Visibility::Hidden;
+    }
+}
+
+fn open_pause_menu(
+    state: Res<State<GameState>>,
+    mut control_events: EventReader<GameControlEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+    trainer_encounter: Res<TrainerEncounter>,
+) {
+    let started = control_events.read()
+        .filter(|e| e.just_pressed())
+        .any(|e| e.control == GameControl::Start);
+    if started && !state.is_changed() && !trainer_encounter.is_active() {
+        next_state.set(state_stack.push(GameState::Pause));
+    }
+}
+
+/// Start closes the pause menu again, like Cancel.
+fn close_pause_menu(
+    state: Res<State<GameState>>,
+    mut control_events: EventReader<GameControlEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+) {
+    let started = control_events.read()
+        .filter(|e| e.just_pressed())
+        .any(|e| e.control == GameControl::Start);
+    if started && !state.is_changed() {
+        next_state.set(state_stack.back());
+    }
+}
+
+fn trigger_resume(
+    resume_query: Query<(), With<ResumeOnTriggered>>,
+    mut events: EventReader<TriggerEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+) {
+    for event in events.read() {
+        if resume_query.contains(event.triggered) {
+            next_state.set(state_stack.back());
+        }
+    }
+}
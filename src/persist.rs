@@ -0,0 +1,112 @@
+//! Small named documents kept between sessions, such as save slots and settings:
+//! one JSON file per name in the platform data directory, or a `localStorage`
+//! entry in the browser.
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PersistError {
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("Could not access file: {0}")]
+    Io(#[from] std::io::Error),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[error("No data directory on this platform")]
+    NoDataDirectory,
+    #[cfg(target_arch = "wasm32")]
+    #[error("Browser storage is unavailable")]
+    StorageUnavailable,
+}
+
+/// Where documents are written: to [`write`] normally, or kept in memory while
+/// replaying, so a replay leaves the files of whoever plays it alone.
+#[derive(Resource, Default, Debug)]
+pub enum Persistence {
+    #[default]
+    Stored,
+    Memory(HashMap<String, String>),
+}
+
+impl Persistence {
+    pub fn write(&mut self, name: &str, contents: &str) -> Result<(), PersistError> {
+        match self {
+            Persistence::Stored => write(name, contents),
+            Persistence::Memory(documents) => {
+                documents.insert(name.to_string(), contents.to_string());
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::*;
+
+#[cfg(target_arch = "wasm32")]
+pub use web::*;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use std::fs;
+    use std::io::ErrorKind;
+    use std::path::PathBuf;
+
+    use super::PersistError;
+
+    const DATA_DIRECTORY: &str = "pocket_daemons";
+
+    fn path(name: &str) -> Result<PathBuf, PersistError> {
+        let directory = dirs::data_dir().ok_or(PersistError::NoDataDirectory)?;
+        Ok(directory.join(DATA_DIRECTORY).join(format!("{}.json", name)))
+    }
+
+    pub fn read(name: &str) -> Result<Option<String>, PersistError> {
+        match fs::read_to_string(path(name)?) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Writes to a temporary file first so a crash mid-write can't leave a
+    /// half-written file behind.
+    pub fn write(name: &str, contents: &str) -> Result<(), PersistError> {
+        let path = path(name)?;
+        if let Some(directory) = path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+        let temporary = path.with_extension("json.tmp");
+        fs::write(&temporary, contents)?;
+        fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod web {
+    use super::PersistError;
+
+    fn key(name: &str) -> String {
+        format!("pocket_daemons.{}", name)
+    }
+
+    fn local_storage() -> Result<web_sys::Storage, PersistError> {
+        web_sys::window()
+            .and_then(|window| window.local_storage().ok().flatten())
+            .ok_or(PersistError::StorageUnavailable)
+    }
+
+    pub fn read(name: &str) -> Result<Option<String>, PersistError> {
+        local_storage()?
+            .get_item(&key(name))
+            .map_err(|_| PersistError::StorageUnavailable)
+    }
+
+    pub fn write(name: &str, contents: &str) -> Result<(), PersistError> {
+        local_storage()?
+            .set_item(&key(name), contents)
+            .map_err(|_| PersistError::StorageUnavailable)
+    }
+}
@@ -8,6 +8,7 @@ use crate::graph::grid_transform::*;
 use crate::mob::*;
 use crate::control::*;
 use crate::map::*;
+use crate::trainer::TrainerEncounter;
 
 pub struct PlayerPlugin;
 
@@ -38,17 +39,24 @@ pub fn spawn_player(
         &mut MovementCooldown,
     ), With<Player>>,
     mut map_and_player_loading: ResMut<MapAndPlayerLoading>,
+    mut spawn_override: ResMut<SpawnOverride>,
+    mut grid_index: ResMut<GridIndex>,
 ) {
     for event in event.read() {
+        let placement = spawn_override.take();
+        let start = match placement {
+            Some((position, _)) => position,
+            None => event.location + event.direction,
+        };
         let player_entity  = match query.get_single_mut() {
             Ok((entity, mut pos, mut last, mut cooldown)) => {
-                *pos = GridPosition(event.location + event.direction);
-                *last = LastGridPosition(event.location + event.direction);
+                *pos = GridPosition(start);
+                *last = LastGridPosition(start);
                 (**cooldown).finish();
                 entity
             }
             Err(QuerySingleError::NoEntities(_)) => {
-                let new_transform_base:Transform = (event.location + event.direction).into();
+                let new_transform_base:Transform = start.into();
                 commands.spawn((
                     Player,
                     Sprite {
@@ -62,31 +70,55 @@ pub fn spawn_player(
                          y: new_transform_base.translation.y, 
                          z: 1.0 
                     }),
-                    GridPosition(event.location + event.direction),
+                    GridPosition(start),
                 )).id()
             }
             Err(QuerySingleError::MultipleEntities(_)) => {
                 panic!("Error: There is more than one player!");
             }
         };
-        mob_move_events.send(MobMoveEvent{
-            entity: player_entity,
-            movement: -event.direction,
-        });
+        match placement {
+            // Placed directly, so nothing walks it into the grid index.
+            Some((_, facing)) => {
+                commands.entity(player_entity).insert(GridDirection(facing));
+                grid_index.update(player_entity, start);
+            }
+            None => {
+                mob_move_events.send(MobMoveEvent{
+                    entity: player_entity,
+                    movement: -event.direction,
+                });
+            }
+        }
         **map_and_player_loading = false;
     } 
 }
 
+/// Holding Run halves the time a step takes.
 pub fn player_move_control(
     mut control_events: EventReader<GameControlEvent>,
     mut mob_move_events: EventWriter<MobMoveEvent>,
-    query: Query<
-        Entity,
+    mut query: Query<
+        (Entity, &mut MovementCooldown),
         With<Player>
     >,
+    trainer_encounter: Res<TrainerEncounter>,
 ) {
-    match control_events.read()
-    .filter(|e| e.pressed())
+    if trainer_encounter.is_active() {
+        control_events.clear();
+        return;
+    }
+    let held: Vec<&GameControlEvent> = control_events.read()
+        .filter(|e| e.pressed())
+        .collect();
+    let step = match held.iter().any(|e| e.control == GameControl::Run) {
+        true => MovementCooldown::WALK / 2,
+        false => MovementCooldown::WALK,
+    };
+    for (_, mut cooldown) in &mut query {
+        cooldown.set_step_duration(step);
+    }
+    match held.into_iter()
     .filter(|e| e.is_movement())
     .nth(0) {
         Some(e) => {
@@ -97,7 +129,7 @@ pub fn player_move_control(
                 GameControl::Right => GridTransform::EAST,
                 _ => panic!("Not a Move Control"),
             };
-            for player in &query {
+            for (player, _) in &query {
                 mob_move_events.send(MobMoveEvent{
                     entity: player,
                     movement: movement,
@@ -116,7 +148,12 @@ pub fn player_interact_control(
         Entity,
         With<Player>
     >,
+    trainer_encounter: Res<TrainerEncounter>,
 ) {
+    if trainer_encounter.is_active() {
+        control_events.clear();
+        return;
+    }
     match control_events.read()
     .filter(|e| e.just_pressed())
     .filter(|e| e.control == GameControl::Interact)
@@ -0,0 +1,358 @@
+//! Recording the controls of a session to a file and playing them back, for
+//! reproducing bug reports.
+//!
+//! Start the game with `--record <file>` to write every [`GameControlEvent`]
+//! to `<file>` on exit, or with `--replay <file>` to feed a recording back in
+//! place of the keyboard and gamepads. Both run at a fixed timestep and seed
+//! [`GameRng`] from the recording, so timers and randomness line up. The
+//! recording also keeps the controls, language and save slots the session
+//! started with, so a replay doesn't depend on the files of whoever plays it.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::control::init_input_map;
+use crate::control::map_inputs_to_control_events;
+use crate::control::GameControlEvent;
+use crate::control::InputMap;
+use crate::options::capture_rebind_key;
+use crate::options::RebindKeyEvent;
+use crate::persist::Persistence;
+use crate::rng::GameRng;
+use crate::save::SaveSlots;
+use crate::text_loading::Language;
+use crate::GameState;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let mode = match ReplayMode::from_args(std::env::args()) {
+            Ok(mode) => mode,
+            Err(error) => {
+                error!("Not replaying: {}", error);
+                ReplayMode::Off
+            }
+        };
+        add_replay_mode(app, mode);
+    }
+}
+
+/// Sets the app up to record, replay or do neither.
+pub fn add_replay_mode(app: &mut App, mode: ReplayMode) {
+    if let Some(seed) = mode.seed() {
+        app
+        .insert_resource(GameRng::seeded(seed))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(FRAME));
+    }
+    // Writes stay in memory even once the replay runs out, as the
+    // settings and saves are still the recorder's.
+    match mode {
+        ReplayMode::Replaying { .. } => app.insert_resource(Persistence::Memory(HashMap::new())),
+        _ => app.init_resource::<Persistence>(),
+    };
+    app
+    .insert_resource(mode)
+    .add_systems(Startup, (
+        record_starting_values
+            .after(init_input_map)
+            .run_if(is_recording),
+        restore_starting_values
+            .after(init_input_map)
+            .run_if(is_replaying),
+    ))
+    .add_systems(Update, (
+        record_controls
+            .after(map_inputs_to_control_events)
+            .run_if(is_recording),
+        play_back_controls
+            .before(map_inputs_to_control_events)
+            .before(capture_rebind_key)
+            .run_if(is_replaying),
+    ).run_if(not(in_state(GameState::TextLoading)).and(not(in_state(GameState::AssetLoading)))))
+    .add_systems(Last, (
+        save_recording_on_exit,
+    ));
+}
+
+/// Time every frame advances by while recording or replaying.
+const FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+#[derive(Error, Debug)]
+pub enum ReplayError {
+    #[error("{0} needs a file")]
+    MissingPath(&'static str),
+    #[error("Could not access recording: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse recording: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// The controls sent on one frame, counted from the end of asset loading.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RecordedFrame {
+    pub frame: u32,
+    pub controls: Vec<GameControlEvent>,
+    /// Keys pressed to rebind a control on the options screen.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rebinds: Vec<RebindKeyEvent>,
+}
+
+/// What the session started with that would otherwise be read from disk.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StartingValues {
+    pub input_map: InputMap,
+    pub language: Language,
+    pub save_slots: SaveSlots,
+}
+
+/// Frames without any controls are left out.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct InputRecording {
+    pub seed: u64,
+    /// Filled in at startup, once the values have been read.
+    pub start: Option<StartingValues>,
+    pub frames: Vec<RecordedFrame>,
+}
+
+#[derive(Resource, Default, Debug)]
+pub enum ReplayMode {
+    #[default]
+    Off,
+    Recording {
+        path: PathBuf,
+        recording: InputRecording,
+        frame: u32,
+    },
+    Replaying {
+        recording: InputRecording,
+        /// Index of the next recorded frame to send.
+        next: usize,
+        frame: u32,
+    },
+}
+
+impl ReplayMode {
+    fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, ReplayError> {
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--record" => {
+                    let path = args.next().ok_or(ReplayError::MissingPath("--record"))?;
+                    return Ok(ReplayMode::Recording {
+                        path: path.into(),
+                        recording: InputRecording {
+                            seed: rand::random(),
+                            start: None,
+                            frames: Vec::new(),
+                        },
+                        frame: 0,
+                    });
+                }
+                "--replay" => {
+                    let path = args.next().ok_or(ReplayError::MissingPath("--replay"))?;
+                    let recording = serde_json::from_str(&fs::read_to_string(path)?)?;
+                    return Ok(ReplayMode::Replaying { recording, next: 0, frame: 0 });
+                }
+                _ => {}
+            }
+        }
+        Ok(ReplayMode::Off)
+    }
+
+    fn seed(&self) -> Option<u64> {
+        match self {
+            ReplayMode::Off => None,
+            ReplayMode::Recording { recording, .. } => Some(recording.seed),
+            ReplayMode::Replaying { recording, .. } => Some(recording.seed),
+        }
+    }
+}
+
+pub fn is_recording(mode: Res<ReplayMode>) -> bool {
+    matches!(*mode, ReplayMode::Recording { .. })
+}
+
+/// While replaying, controls come from the recording instead of input devices.
+pub fn is_replaying(mode: Option<Res<ReplayMode>>) -> bool {
+    matches!(mode.as_deref(), Some(ReplayMode::Replaying { .. }))
+}
+
+fn record_starting_values(
+    mut mode: ResMut<ReplayMode>,
+    input_map: Res<InputMap>,
+    language: Res<Language>,
+    save_slots: Res<SaveSlots>,
+) {
+    let ReplayMode::Recording { recording, .. } = &mut *mode else {
+        return;
+    };
+    recording.start = Some(StartingValues {
+        input_map: input_map.clone(),
+        language: language.clone(),
+        save_slots: save_slots.clone(),
+    });
+}
+
+fn restore_starting_values(
+    mode: Res<ReplayMode>,
+    mut commands: Commands,
+) {
+    let ReplayMode::Replaying { recording, .. } = &*mode else {
+        return;
+    };
+    let Some(start) = &recording.start else {
+        warn!("Recording has no starting values, replaying with the local ones");
+        return;
+    };
+    commands.insert_resource(start.input_map.clone());
+    commands.insert_resource(start.language.clone());
+    commands.insert_resource(start.save_slots.clone());
+}
+
+fn record_controls(
+    mut mode: ResMut<ReplayMode>,
+    mut control_events: EventReader<GameControlEvent>,
+    mut rebind_events: EventReader<RebindKeyEvent>,
+) {
+    let ReplayMode::Recording { recording, frame, .. } = &mut *mode else {
+        return;
+    };
+    let controls: Vec<GameControlEvent> = control_events.read().copied().collect();
+    let rebinds: Vec<RebindKeyEvent> = rebind_events.read().copied().collect();
+    if !controls.is_empty() || !rebinds.is_empty() {
+        recording.frames.push(RecordedFrame { frame: *frame, controls, rebinds });
+    }
+    *frame += 1;
+}
+
+/// Sends the recorded controls of this frame. Once the recording runs out,
+/// control goes back to the input devices.
+fn play_back_controls(
+    mut mode: ResMut<ReplayMode>,
+    mut control: EventWriter<GameControlEvent>,
+    mut rebind: EventWriter<RebindKeyEvent>,
+) {
+    let ReplayMode::Replaying { recording, next, frame } = &mut *mode else {
+        return;
+    };
+    if let Some(recorded) = recording.frames.get(*next).filter(|recorded| recorded.frame == *frame) {
+        control.send_batch(recorded.controls.iter().copied());
+        rebind.send_batch(recorded.rebinds.iter().copied());
+        *next += 1;
+    }
+    *frame += 1;
+    if *next >= recording.frames.len() {
+        info!("Replay finished after {} frames", frame);
+        *mode = ReplayMode::Off;
+    }
+}
+
+fn save_recording_on_exit(
+    mode: Res<ReplayMode>,
+    mut exit_events: EventReader<AppExit>,
+) {
+    if exit_events.read().next().is_none() {
+        return;
+    }
+    let ReplayMode::Recording { path, recording, .. } = &*mode else {
+        return;
+    };
+    let result = serde_json::to_string(recording)
+        .map_err(ReplayError::from)
+        .and_then(|json| Ok(fs::write(path, json)?));
+    match result {
+        Ok(()) => info!("Recorded {} frames to {}", recording.frames.len(), path.display()),
+        Err(error) => error!("Could not save recording: {}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::ControlStatus;
+    use crate::control::GameControl;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    fn press(control: GameControl) -> GameControlEvent {
+        GameControlEvent { control, status: ControlStatus::JustPressed }
+    }
+
+    #[test]
+    fn reading_the_arguments() {
+        assert!(matches!(ReplayMode::from_args(args(&["game"])), Ok(ReplayMode::Off)));
+        let Ok(ReplayMode::Recording { path, recording, frame: 0 }) =
+            ReplayMode::from_args(args(&["game", "--record", "bug.json"]))
+        else {
+            panic!("expected to record");
+        };
+        assert_eq!(path, PathBuf::from("bug.json"));
+        assert!(recording.start.is_none() && recording.frames.is_empty());
+
+        assert!(matches!(
+            ReplayMode::from_args(args(&["game", "--record"])),
+            Err(ReplayError::MissingPath("--record")),
+        ));
+        assert!(matches!(
+            ReplayMode::from_args(args(&["game", "--replay"])),
+            Err(ReplayError::MissingPath("--replay")),
+        ));
+        assert!(matches!(
+            ReplayMode::from_args(args(&["game", "--replay", "no/such/recording.json"])),
+            Err(ReplayError::Io(_)),
+        ));
+    }
+
+    #[test]
+    fn controls_are_played_back_on_their_frame() {
+        let mut app = App::new();
+        app
+        .add_event::<GameControlEvent>()
+        .add_event::<RebindKeyEvent>()
+        .insert_resource(ReplayMode::Replaying {
+            recording: InputRecording {
+                seed: 7,
+                start: None,
+                frames: vec![
+                    RecordedFrame { frame: 0, controls: vec![press(GameControl::Up)], rebinds: vec![] },
+                    RecordedFrame {
+                        frame: 2,
+                        controls: vec![press(GameControl::Interact)],
+                        rebinds: vec![RebindKeyEvent(KeyCode::KeyK)],
+                    },
+                ],
+            },
+            next: 0,
+            frame: 0,
+        })
+        .add_systems(Update, play_back_controls.run_if(is_replaying));
+
+        let mut sent = Vec::new();
+        for _ in 0..4 {
+            app.update();
+            let world = app.world_mut();
+            let controls: Vec<GameControlEvent> =
+                world.resource_mut::<Events<GameControlEvent>>().drain().collect();
+            let rebinds: Vec<RebindKeyEvent> =
+                world.resource_mut::<Events<RebindKeyEvent>>().drain().collect();
+            sent.push((controls, rebinds));
+        }
+        assert_eq!(sent, vec![
+            (vec![press(GameControl::Up)], vec![]),
+            (vec![], vec![]),
+            (vec![press(GameControl::Interact)], vec![RebindKeyEvent(KeyCode::KeyK)]),
+            (vec![], vec![]),
+        ]);
+        assert!(matches!(app.world().resource::<ReplayMode>(), ReplayMode::Off));
+    }
+}
@@ -0,0 +1,331 @@
+mod document;
+
+use bevy::prelude::*;
+use bevy::reflect::Struct;
+use bevy::utils::SystemTime;
+use bevy_ecs_tiled::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::dialog::CurrentDialog;
+use crate::graph::grid_transform::GridTransform;
+use crate::inventory::Inventory;
+use crate::loading::MapAssets;
+use crate::map::ChangeMapEvent;
+use crate::map::ChangeMapQueue;
+use crate::map::CurrentMap;
+use crate::map::CurrentSpawn;
+use crate::map::SpawnOverride;
+use crate::map::TriggerOnInteract;
+use crate::mob::GridDirection;
+use crate::mob::GridPosition;
+use crate::mob::TriggerEvent;
+use crate::party::Party;
+use crate::persist;
+use crate::persist::PersistError;
+use crate::persist::Persistence;
+use crate::player::Player;
+use crate::state_stack::StateStack;
+use crate::storage::DaemonStorage;
+use crate::story_flags::StoryFlags;
+use crate::text_loading::GameText;
+use crate::GameState;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(Update, (
+            save_at_save_point,
+        ).run_if(in_state(GameState::Playing)))
+        .add_systems(Update, (
+            load_game,
+        ))
+        .init_resource::<SaveSlots>()
+        .init_resource::<ActiveSaveSlot>()
+        .register_type::<SavePoint>()
+        .add_event::<LoadGameEvent>();
+    }
+}
+
+/// Number of save slots.
+pub const SAVE_SLOTS: usize = 3;
+
+/// Spawn point used if a save somehow has none.
+const FALLBACK_SPAWN: &str = "start";
+
+#[derive(Error, Debug)]
+pub enum SaveError {
+    #[error("Could not access save: {0}")]
+    Persist(#[from] PersistError),
+    #[error("Could not parse save: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Save version {0} is newer than this game supports")]
+    UnsupportedVersion(u32),
+    #[error("Save is malformed: {0}")]
+    Malformed(&'static str),
+    #[error("Save is corrupted: checksum {found} does not match {expected}")]
+    ChecksumMismatch { expected: String, found: String },
+}
+
+/// Everything written to a save slot.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SaveData {
+    /// Seconds since the Unix epoch, used to find the latest save.
+    pub saved_at: u64,
+    /// Field name of the map in [`MapAssets`].
+    pub map: String,
+    pub spawn: Option<String>,
+    pub position: GridTransform,
+    pub direction: GridTransform,
+    pub party: Party,
+    pub inventory: Inventory,
+    pub story_flags: StoryFlags,
+    pub storage: DaemonStorage,
+}
+
+impl SaveData {
+    pub fn to_json(&self) -> Result<String, SaveError> {
+        document::encode(self)
+    }
+
+    /// Parses a save of any known version, upgrading it to the current layout.
+    pub fn from_json(json: &str) -> Result<Self, SaveError> {
+        document::decode(json)
+    }
+}
+
+/// What is in each slot, read once at startup and kept in step with saves.
+#[derive(Resource, Debug, Clone, PartialEq, Deref, Serialize, Deserialize)]
+pub struct SaveSlots(Vec<Option<SaveData>>);
+
+impl Default for SaveSlots {
+    fn default() -> Self {
+        let slots = (0..SAVE_SLOTS)
+            .map(|slot| match read_slot(slot) {
+                Ok(data) => data,
+                Err(error) => {
+                    warn!("Ignoring save slot {}: {}", slot, error);
+                    None
+                }
+            })
+            .collect();
+        SaveSlots(slots)
+    }
+}
+
+impl SaveSlots {
+    /// The slot saved to most recently.
+    pub fn latest(&self) -> Option<usize> {
+        self.iter()
+            .enumerate()
+            .filter_map(|(slot, data)| data.as_ref().map(|data| (slot, data.saved_at)))
+            .max_by_key(|(_, saved_at)| *saved_at)
+            .map(|(slot, _)| slot)
+    }
+
+    /// An empty slot, or the oldest one if all are taken.
+    pub fn free_or_oldest(&self) -> usize {
+        self.iter()
+            .position(Option::is_none)
+            .or_else(|| self.iter()
+                .enumerate()
+                .min_by_key(|(_, data)| data.as_ref().map_or(0, |data| data.saved_at))
+                .map(|(slot, _)| slot))
+            .unwrap_or(0)
+    }
+}
+
+/// Slot the current game saves to; `None` until a new game first saves.
+#[derive(Resource, Default, Debug, Deref, DerefMut)]
+pub struct ActiveSaveSlot(Option<usize>);
+
+/// Saves the game when interacted with, authored in Tiled.
+#[derive(Component, Default, Debug, Reflect)]
+#[reflect(Component, Default)]
+#[require(TriggerOnInteract)]
+pub struct SavePoint;
+
+#[derive(Event, Debug)]
+pub struct LoadGameEvent {
+    pub slot: usize,
+}
+
+fn slot_name(slot: usize) -> String {
+    format!("slot_{}", slot)
+}
+
+fn read_slot(slot: usize) -> Result<Option<SaveData>, SaveError> {
+    persist::read(&slot_name(slot))?
+        .map(|json| SaveData::from_json(&json))
+        .transpose()
+}
+
+/// Name of the [`MapAssets`] field holding `handle`.
+fn map_name(map_assets: &MapAssets, handle: &Handle<TiledMap>) -> Option<String> {
+    (0..map_assets.field_len()).find_map(|index| {
+        let field = map_assets.field_at(index)?.try_downcast_ref::<Handle<TiledMap>>()?;
+        (field == handle).then(|| map_assets.name_at(index).map(str::to_string)).flatten()
+    })
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+fn save_at_save_point(
+    save_point_query: Query<(), With<SavePoint>>,
+    player_query: Query<(&GridPosition, &GridDirection), With<Player>>,
+    mut events: EventReader<TriggerEvent>,
+    current_map: Res<CurrentMap>,
+    current_spawn: Res<CurrentSpawn>,
+    map_assets: Res<MapAssets>,
+    (party, inventory, story_flags, storage): (Res<Party>, Res<Inventory>, Res<StoryFlags>, Res<DaemonStorage>),
+    mut save_slots: ResMut<SaveSlots>,
+    mut active_slot: ResMut<ActiveSaveSlot>,
+    mut persistence: ResMut<Persistence>,
+    game_text: Res<GameText>,
+    mut current_dialog: ResMut<CurrentDialog>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+) {
+    for event in events.read() {
+        if !save_point_query.contains(event.triggered) {
+            continue;
+        }
+        let (Ok((position, direction)), Some(map)) = (
+            player_query.get(event.triggering),
+            (**current_map).as_ref().and_then(|handle| map_name(&map_assets, handle)),
+        ) else {
+            continue;
+        };
+
+        let data = SaveData {
+            saved_at: now(),
+            map,
+            spawn: (**current_spawn).clone(),
+            position: **position,
+            direction: **direction,
+            party: party.clone(),
+            inventory: inventory.clone(),
+            story_flags: story_flags.clone(),
+            storage: storage.clone(),
+        };
+        let slot = active_slot.unwrap_or_else(|| save_slots.free_or_oldest());
+        let result = data.to_json()
+            .and_then(|json| Ok(persistence.write(&slot_name(slot), &json)?));
+        let dialog = match result {
+            Ok(()) => {
+                save_slots.0[slot] = Some(data);
+                **active_slot = Some(slot);
+                game_text.dialog_or_placeholder("game_saved")
+            }
+            Err(error) => {
+                error!("Could not save to slot {}: {}", slot, error);
+                game_text.dialog_or_placeholder("save_failed")
+            }
+        };
+        *current_dialog = CurrentDialog(Some(dialog));
+        next_state.set(state_stack.push(GameState::Dialog));
+        return;
+    }
+}
+
+fn load_game(
+    mut events: EventReader<LoadGameEvent>,
+    save_slots: Res<SaveSlots>,
+    map_assets: Res<MapAssets>,
+    mut party: ResMut<Party>,
+    mut inventory: ResMut<Inventory>,
+    mut story_flags: ResMut<StoryFlags>,
+    mut storage: ResMut<DaemonStorage>,
+    mut active_slot: ResMut<ActiveSaveSlot>,
+    mut spawn_override: ResMut<SpawnOverride>,
+    mut change_map_queue: ResMut<ChangeMapQueue>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+) {
+    let Some(event) = events.read().last() else {
+        return;
+    };
+    let Some(data) = save_slots.get(event.slot).and_then(Option::as_ref) else {
+        warn!("Save slot {} is empty", event.slot);
+        return;
+    };
+    let Some(map) = map_assets.get_field::<Handle<TiledMap>>(&data.map) else {
+        error!("Save slot {} is on unknown map `{}`", event.slot, data.map);
+        return;
+    };
+
+    *party = data.party.clone();
+    *inventory = data.inventory.clone();
+    *story_flags = data.story_flags.clone();
+    *storage = data.storage.clone();
+    **active_slot = Some(event.slot);
+    **spawn_override = Some((data.position, data.direction));
+    change_map_queue.push(ChangeMapEvent {
+        map: map.clone(),
+        spawn: data.spawn.clone().unwrap_or_else(|| FALLBACK_SPAWN.to_string()),
+    });
+    next_state.set(state_stack.push(GameState::Playing));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::ControlStatus;
+    use crate::control::GameControl;
+    use crate::control::GameControlEvent;
+    use crate::options::RebindKeyEvent;
+    use crate::replay::add_replay_mode;
+    use crate::replay::InputRecording;
+    use crate::replay::RecordedFrame;
+    use crate::replay::ReplayMode;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn replayed_saves_stay_in_memory() {
+        let interact = [ControlStatus::JustPressed, ControlStatus::Pressed]
+            .map(|status| GameControlEvent { control: GameControl::Interact, status });
+        let mut app = TestApp::new();
+        add_replay_mode(&mut app.app, ReplayMode::Replaying {
+            recording: InputRecording {
+                seed: 7,
+                start: None,
+                frames: vec![RecordedFrame { frame: 1, controls: interact.to_vec(), rebinds: vec![] }],
+            },
+            next: 0,
+            frame: 0,
+        });
+        app.app
+        .add_event::<RebindKeyEvent>()
+        .insert_resource(MapAssets { road: Handle::default(), clearing: Handle::default() })
+        .insert_resource(SaveSlots(vec![None; SAVE_SLOTS]))
+        .init_resource::<ActiveSaveSlot>()
+        .init_resource::<CurrentMap>()
+        .init_resource::<CurrentSpawn>()
+        .init_resource::<Party>()
+        .init_resource::<Inventory>()
+        .init_resource::<StoryFlags>()
+        .init_resource::<DaemonStorage>()
+        .init_resource::<GameText>()
+        .init_resource::<CurrentDialog>()
+        .add_systems(Update, save_at_save_point.run_if(in_state(GameState::Playing)));
+        **app.app.world_mut().resource_mut::<CurrentMap>() = Some(Handle::default());
+        app.spawn_player(GridTransform::ZERO);
+        app.spawn_at(GridTransform::SOUTH, SavePoint);
+
+        app.tick(3);
+        let slot = app.app.world().resource::<SaveSlots>()[0].clone()
+            .expect("the replay should have saved");
+        assert_eq!(slot.map, "road");
+        let Persistence::Memory(documents) = app.app.world().resource::<Persistence>() else {
+            panic!("a replay should write to memory");
+        };
+        assert!(documents.contains_key(&slot_name(0)));
+    }
+}
@@ -0,0 +1,138 @@
+//! On-disk layout of a save: a header with the schema version and a checksum,
+//! wrapped around the [`SaveData`] itself.
+//!
+//! Older documents are upgraded one version at a time by [`MIGRATIONS`] before
+//! they are deserialized, so [`SaveData`] only ever has to match the latest layout.
+
+use serde::Deserialize;
+use serde::Serialize;
+use serde_json::Value;
+
+use super::SaveData;
+use super::SaveError;
+
+/// Current schema version of save documents.
+pub const SAVE_VERSION: u32 = 2;
+
+/// Upgrades `data` from the version at its index plus one to the next.
+type Migration = fn(Value) -> Result<Value, SaveError>;
+
+const MIGRATIONS: [Migration; SAVE_VERSION as usize - 1] = [
+    migrate_v1_to_v2,
+];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct SaveHeader {
+    version: u32,
+    /// FNV-1a hash of the compact JSON of `data`, as hex.
+    checksum: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct SaveDocument {
+    header: SaveHeader,
+    data: Value,
+}
+
+fn checksum(data: &Value) -> String {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let hash = data.to_string().bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(PRIME)
+    });
+    format!("{:016x}", hash)
+}
+
+pub fn encode(save: &SaveData) -> Result<String, SaveError> {
+    let data = serde_json::to_value(save)?;
+    let document = SaveDocument {
+        header: SaveHeader {
+            version: SAVE_VERSION,
+            checksum: checksum(&data),
+        },
+        data,
+    };
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+pub fn decode(json: &str) -> Result<SaveData, SaveError> {
+    let mut document: Value = serde_json::from_str(json)?;
+    let (version, mut data) = match document.get_mut("header") {
+        Some(header) => {
+            let header: SaveHeader = serde_json::from_value(header.take())?;
+            let data = document.get_mut("data")
+                .map(Value::take)
+                .ok_or(SaveError::Malformed("missing data"))?;
+            let found = checksum(&data);
+            if found != header.checksum {
+                return Err(SaveError::ChecksumMismatch {
+                    expected: header.checksum,
+                    found,
+                });
+            }
+            (header.version, data)
+        }
+        // Version 1 saves were the bare data with a version field and no checksum.
+        None => {
+            let version = document.get("version")
+                .and_then(Value::as_u64)
+                .ok_or(SaveError::Malformed("missing header"))?;
+            (version as u32, document)
+        }
+    };
+
+    if version == 0 {
+        return Err(SaveError::Malformed("version 0"));
+    }
+    if version > SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+    for migration in &MIGRATIONS[version as usize - 1..] {
+        data = migration(data)?;
+    }
+    Ok(serde_json::from_value(data)?)
+}
+
+/// The version moved from the data into the header.
+fn migrate_v1_to_v2(mut data: Value) -> Result<Value, SaveError> {
+    data.as_object_mut()
+        .ok_or(SaveError::Malformed("data is not an object"))?
+        .remove("version");
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const V1: &str = include_str!("../../tests/fixtures/saves/v1.json");
+    const V2: &str = include_str!("../../tests/fixtures/saves/v2.json");
+    const V2_CORRUPT: &str = include_str!("../../tests/fixtures/saves/v2_corrupt.json");
+
+    #[test]
+    fn round_trip() {
+        let save = decode(V2).unwrap();
+        assert_eq!(decode(&encode(&save).unwrap()).unwrap(), save);
+    }
+
+    #[test]
+    fn old_versions_migrate() {
+        let old = decode(V1).unwrap();
+        let current = decode(V2).unwrap();
+        assert_eq!(old, current);
+        assert_eq!(old.map, "road");
+        assert_eq!(old.party.members()[0].species, "emberkit");
+    }
+
+    #[test]
+    fn corruption_is_reported() {
+        assert!(matches!(decode(V2_CORRUPT), Err(SaveError::ChecksumMismatch { .. })));
+        assert!(matches!(decode("{\"header\": 3"), Err(SaveError::Json(_))));
+    }
+
+    #[test]
+    fn newer_versions_are_rejected() {
+        let newer = V1.replacen("\"version\": 1", "\"version\": 99", 1);
+        assert!(matches!(decode(&newer), Err(SaveError::UnsupportedVersion(99))));
+    }
+}
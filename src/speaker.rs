@@ -0,0 +1,122 @@
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::helpers::ron_asset::RonAssetLoader;
+use crate::loading::DialogAssets;
+use crate::loading::TextureAssets;
+use crate::text_loading::Dialog;
+use crate::text_loading::GameText;
+use crate::GameState;
+
+pub struct SpeakerPlugin;
+
+impl Plugin for SpeakerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(OnExit(GameState::AssetLoading), (
+            check_speakers,
+        ))
+        .init_asset::<SpeakerList>()
+        .register_asset_loader(RonAssetLoader::<SpeakerList>::new(&["speakers.ron"]));
+    }
+}
+
+/// Mood a portrait falls back to when it has no frame for the page's mood.
+pub const DEFAULT_MOOD: &str = "neutral";
+
+#[derive(Reflect, Debug, Deserialize, Clone)]
+pub struct SpeakerDefinition {
+    /// Shown on the name plate above the dialog box. Speakers without a
+    /// name, like signs, get no plate.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub portrait: Option<Portrait>,
+}
+
+/// Portrait frames, looked up by field name on [`TextureAssets`] the same way
+/// [`InitSprite`](crate::map::InitSprite) does.
+#[derive(Reflect, Debug, Deserialize, Clone)]
+pub struct Portrait {
+    pub reference: String,
+    pub atlas_reference: String,
+    /// Atlas index for each mood.
+    pub moods: HashMap<String, usize>,
+}
+
+impl Portrait {
+    /// The frame for `mood`, or for [`DEFAULT_MOOD`] if there is none.
+    pub fn index(&self, mood: &str) -> Option<usize> {
+        self.moods.get(mood)
+            .or_else(|| self.moods.get(DEFAULT_MOOD))
+            .copied()
+    }
+}
+
+/// Every speaker keyed by [`Page::speaker`](crate::text_loading::Page::speaker).
+#[derive(Asset, Reflect, Debug, Deserialize, Deref, DerefMut, Clone)]
+pub struct SpeakerList(HashMap<String, SpeakerDefinition>);
+
+/// Speakers in `dialogs` that aren't in `speakers`, sorted.
+pub fn unknown_speakers<'a>(
+    speakers: &SpeakerList,
+    dialogs: impl IntoIterator<Item = &'a Dialog>,
+) -> Vec<String> {
+    let unknown: BTreeSet<&String> = dialogs.into_iter()
+        .flat_map(|dialog| dialog.iter())
+        .map(|page| &page.speaker)
+        .filter(|speaker| !speakers.contains_key(*speaker))
+        .collect();
+    unknown.into_iter().cloned().collect()
+}
+
+/// Warns about speakers in the game text with no definition, and portraits
+/// that reference missing textures.
+fn check_speakers(
+    dialog_assets: Res<DialogAssets>,
+    speaker_lists: Res<Assets<SpeakerList>>,
+    textures: Res<TextureAssets>,
+    game_text: Res<GameText>,
+) {
+    let Some(speakers) = speaker_lists.get(&dialog_assets.speakers) else {
+        warn!("Speaker list is not loaded");
+        return;
+    };
+
+    let unknown = unknown_speakers(speakers, game_text.dialogs.values());
+    if !unknown.is_empty() {
+        warn!("Dialog has unknown speakers: {}", unknown.join(", "));
+    }
+
+    for (id, speaker) in speakers.iter() {
+        let Some(portrait) = &speaker.portrait else { continue };
+        if textures.get_field::<Handle<Image>>(&portrait.reference).is_none()
+            || textures.get_field::<Handle<TextureAtlasLayout>>(&portrait.atlas_reference).is_none()
+        {
+            warn!(
+                "Speaker `{}` has unknown portrait `{}` / `{}`",
+                id, portrait.reference, portrait.atlas_reference
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::text_loading::read_locale;
+
+    #[test]
+    fn speakers_parse() {
+        let speakers: SpeakerList = ron::de::from_str(include_str!("../assets/dialog/speakers.speakers.ron")).unwrap();
+        let game_text = read_locale("en-US");
+        assert!(unknown_speakers(&speakers, game_text.dialogs.values()).is_empty());
+
+        let portrait = speakers["hiker"].portrait.as_ref().unwrap();
+        assert_eq!(portrait.index("mad"), Some(1));
+        assert_eq!(portrait.index("sleepy"), portrait.index(DEFAULT_MOOD));
+    }
+}
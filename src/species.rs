@@ -0,0 +1,324 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::battle::damage::TypeChart;
+use crate::helpers::ron_asset::RonAssetLoader;
+use crate::loading::DaemonAssets;
+use crate::party::GrowthRate;
+
+pub struct SpeciesPlugin;
+
+impl Plugin for SpeciesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_asset::<Species>()
+        .init_asset::<MoveList>()
+        .init_asset::<TypeChart>()
+        .register_asset_loader(RonAssetLoader::<Species>::new(&["species.ron"]))
+        .register_asset_loader(RonAssetLoader::<MoveList>::new(&["moves.ron"]))
+        .register_asset_loader(RonAssetLoader::<TypeChart>::new(&["types.ron"]))
+        .register_type::<SpeciesRegistry>();
+    }
+}
+
+/// A daemon species, loaded from `daemons/species/*.species.ron`.
+#[derive(Asset, Reflect, Debug, Deserialize, Clone)]
+pub struct Species {
+    pub id: String,
+    pub name: String,
+    pub types: Vec<String>,
+    pub base_stats: BaseStats,
+    pub learnset: Vec<LearnsetEntry>,
+    pub sprite: SpeciesSprite,
+    #[serde(default)]
+    pub evolutions: Vec<Evolution>,
+    #[serde(default)]
+    pub growth_rate: GrowthRate,
+    /// How easy this species is to capture, from 1 to 255.
+    #[serde(default = "default_catch_rate")]
+    pub catch_rate: u8,
+    /// Base experience for defeating this species.
+    #[serde(default = "default_xp_yield")]
+    pub xp_yield: u16,
+}
+
+fn default_xp_yield() -> u16 { 64 }
+fn default_catch_rate() -> u8 { 120 }
+
+#[derive(Reflect, Debug, Deserialize, Clone, Copy, Default)]
+pub struct BaseStats {
+    pub hp: u16,
+    pub attack: u16,
+    pub defense: u16,
+    pub sp_attack: u16,
+    pub sp_defense: u16,
+    pub speed: u16,
+}
+
+#[derive(Reflect, Debug, Deserialize, Clone)]
+pub struct LearnsetEntry {
+    pub level: u8,
+    #[serde(rename = "move")]
+    pub move_id: String,
+}
+
+/// Sprite references, looked up by field name on [`TextureAssets`](crate::loading::TextureAssets)
+/// the same way [`InitSprite`](crate::map::InitSprite) does.
+#[derive(Reflect, Debug, Deserialize, Clone)]
+pub struct SpeciesSprite {
+    pub reference: String,
+    pub atlas_reference: String,
+    #[serde(default)]
+    pub index: usize,
+}
+
+#[derive(Reflect, Debug, Deserialize, Clone)]
+pub struct Evolution {
+    pub into: String,
+    pub condition: EvolutionCondition,
+}
+
+#[derive(Reflect, Debug, Deserialize, Clone, PartialEq)]
+pub enum EvolutionCondition {
+    Level(u8),
+}
+
+#[derive(Reflect, Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum MoveCategory {
+    Physical,
+    Special,
+    Status,
+}
+
+#[derive(Reflect, Debug, Deserialize, Clone)]
+pub struct MoveData {
+    pub name: String,
+    pub element: String,
+    pub category: MoveCategory,
+    pub power: u16,
+    pub accuracy: u8,
+    pub pp: u8,
+    #[serde(default)]
+    pub priority: i8,
+}
+
+/// Every move in the game keyed by id, loaded from `daemons/moves.moves.ron`.
+#[derive(Asset, Reflect, Debug, Deserialize, Deref, DerefMut, Clone)]
+pub struct MoveList(HashMap<String, MoveData>);
+
+#[derive(Debug, Error, PartialEq)]
+pub enum SpeciesValidationError {
+    #[error("species `{species}` is defined more than once")]
+    DuplicateSpecies { species: String },
+    #[error("species `{species}` references unknown type `{reference}`")]
+    UnknownSpeciesType { species: String, reference: String },
+    #[error("species `{species}` references unknown move `{reference}`")]
+    UnknownMove { species: String, reference: String },
+    #[error("species `{species}` evolves into unknown species `{reference}`")]
+    UnknownEvolution { species: String, reference: String },
+    #[error("move `{move_id}` references unknown type `{reference}`")]
+    UnknownMoveType { move_id: String, reference: String },
+    #[error("type chart matchup references unknown type `{reference}`")]
+    UnknownMatchupType { reference: String },
+}
+
+/// All validated species and the move and type data they depend on.
+///
+/// Built once the [`DaemonAssets`] collection has loaded. Entries that fail
+/// validation are logged and left out rather than aborting the load.
+#[derive(Resource, Reflect, Debug)]
+pub struct SpeciesRegistry {
+    pub species: HashMap<String, Species>,
+    pub moves: HashMap<String, MoveData>,
+    pub types: TypeChart,
+}
+
+impl SpeciesRegistry {
+    pub fn build(
+        species: impl IntoIterator<Item = Species>,
+        moves: MoveList,
+        types: TypeChart,
+    ) -> (Self, Vec<SpeciesValidationError>) {
+        let mut errors = Vec::new();
+
+        let mut unknown_matchups: Vec<&String> = types.matchups.iter()
+            .flat_map(|(attack, row)| std::iter::once(attack).chain(row.keys()))
+            .filter(|element| !types.contains(element))
+            .collect();
+        unknown_matchups.sort();
+        unknown_matchups.dedup();
+        errors.extend(unknown_matchups.into_iter().map(|element| {
+            SpeciesValidationError::UnknownMatchupType {
+                reference: element.clone(),
+            }
+        }));
+
+        let moves: HashMap<String, MoveData> = moves.0.into_iter()
+            .filter(|(move_id, data)| {
+                if types.contains(&data.element) {
+                    return true;
+                }
+                errors.push(SpeciesValidationError::UnknownMoveType {
+                    move_id: move_id.clone(),
+                    reference: data.element.clone(),
+                });
+                false
+            })
+            .collect();
+
+        let mut candidates: HashMap<String, Species> = HashMap::new();
+        for entry in species {
+            if candidates.contains_key(&entry.id) {
+                errors.push(SpeciesValidationError::DuplicateSpecies {
+                    species: entry.id.clone(),
+                });
+                continue;
+            }
+            candidates.insert(entry.id.clone(), entry);
+        }
+
+        let mut valid = HashMap::new();
+        for (id, entry) in candidates {
+            let mut entry_errors = Vec::new();
+            for element in &entry.types {
+                if !types.contains(element) {
+                    entry_errors.push(SpeciesValidationError::UnknownSpeciesType {
+                        species: id.clone(),
+                        reference: element.clone(),
+                    });
+                }
+            }
+            for learn in &entry.learnset {
+                if !moves.contains_key(&learn.move_id) {
+                    entry_errors.push(SpeciesValidationError::UnknownMove {
+                        species: id.clone(),
+                        reference: learn.move_id.clone(),
+                    });
+                }
+            }
+            if entry_errors.is_empty() {
+                valid.insert(id, entry);
+            }
+            errors.extend(entry_errors);
+        }
+
+        // Evolutions have to lead to a species that passed. Dropping one can
+        // strand the species that evolve into it, so check until none do.
+        loop {
+            let mut dangling: Vec<(String, String)> = valid.iter()
+                .flat_map(|(id, entry)| entry.evolutions.iter()
+                    .filter(|evolution| !valid.contains_key(&evolution.into))
+                    .map(|evolution| (id.clone(), evolution.into.clone())))
+                .collect();
+            if dangling.is_empty() {
+                break;
+            }
+            dangling.sort();
+            for (species, reference) in dangling {
+                valid.remove(&species);
+                errors.push(SpeciesValidationError::UnknownEvolution { species, reference });
+            }
+        }
+
+        (
+            SpeciesRegistry {
+                species: valid,
+                moves,
+                types,
+            },
+            errors,
+        )
+    }
+}
+
+impl FromWorld for SpeciesRegistry {
+    fn from_world(world: &mut World) -> Self {
+        let daemon_assets = world.resource::<DaemonAssets>();
+        let species_assets = world.resource::<Assets<Species>>();
+        let move_assets = world.resource::<Assets<MoveList>>();
+        let type_assets = world.resource::<Assets<TypeChart>>();
+
+        let species = daemon_assets.species.iter()
+            .filter_map(|handle| species_assets.get(handle))
+            .cloned();
+        let moves = move_assets.get(&daemon_assets.moves)
+            .cloned()
+            .expect("Move list should be loaded with DaemonAssets");
+        let types = type_assets.get(&daemon_assets.types)
+            .cloned()
+            .expect("Type chart should be loaded with DaemonAssets");
+
+        let (registry, errors) = SpeciesRegistry::build(species, moves, types);
+        for error in &errors {
+            error!("Invalid daemon data: {}", error);
+        }
+        info!("Loaded {} daemon species", registry.species.len());
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn species(id: &str, types: &str, moves: &str, evolutions: &str) -> Species {
+        ron::de::from_str(&format!(r#"Species(
+            id: "{id}",
+            name: "{id}",
+            types: [{types}],
+            base_stats: BaseStats(hp: 40, attack: 40, defense: 40, sp_attack: 40, sp_defense: 40, speed: 40),
+            learnset: [{moves}],
+            sprite: SpeciesSprite(reference: "mob", atlas_reference: "mob_layout"),
+            evolutions: [{evolutions}],
+        )"#)).unwrap()
+    }
+
+    fn build(entries: Vec<Species>) -> (SpeciesRegistry, Vec<SpeciesValidationError>) {
+        let moves: MoveList = ron::de::from_str(r#"MoveList({
+            "tackle": MoveData(name: "Tackle", element: "normal", category: Physical, power: 40, accuracy: 100, pp: 35),
+        })"#).unwrap();
+        let types: TypeChart = ron::de::from_str(r#"TypeChart(types: ["normal"])"#).unwrap();
+        SpeciesRegistry::build(entries, moves, types)
+    }
+
+    const TACKLE: &str = r#"LearnsetEntry(level: 1, move: "tackle")"#;
+
+    #[test]
+    fn unknown_references_drop_the_species() {
+        let (registry, errors) = build(vec![
+            species("plain", r#""normal""#, TACKLE, ""),
+            species("odd_move", r#""normal""#, r#"LearnsetEntry(level: 1, move: "fly")"#, ""),
+            species("odd_type", r#""cosmic""#, TACKLE, ""),
+        ]);
+        assert_eq!(registry.species.keys().collect::<Vec<_>>(), ["plain"]);
+        assert!(errors.contains(&SpeciesValidationError::UnknownMove {
+            species: "odd_move".to_string(),
+            reference: "fly".to_string(),
+        }));
+        assert!(errors.contains(&SpeciesValidationError::UnknownSpeciesType {
+            species: "odd_type".to_string(),
+            reference: "cosmic".to_string(),
+        }));
+    }
+
+    #[test]
+    fn evolutions_into_rejected_species_are_dropped() {
+        let evolves_into = |into: &str| format!(r#"Evolution(into: "{into}", condition: Level(16))"#);
+        let (registry, errors) = build(vec![
+            species("first", r#""normal""#, TACKLE, &evolves_into("second")),
+            species("second", r#""normal""#, TACKLE, &evolves_into("third")),
+            species("third", r#""normal""#, r#"LearnsetEntry(level: 1, move: "fly")"#, ""),
+            species("lonely", r#""normal""#, TACKLE, &evolves_into("nobody")),
+        ]);
+        assert!(registry.species.is_empty());
+        for (species, reference) in [("first", "second"), ("second", "third"), ("lonely", "nobody")] {
+            assert!(errors.contains(&SpeciesValidationError::UnknownEvolution {
+                species: species.to_string(),
+                reference: reference.to_string(),
+            }));
+        }
+    }
+}
@@ -1,11 +1,21 @@
 use bevy::prelude::*;
 use crate::GameState;
+use crate::control::GameControl;
+use crate::control::GameControlEvent;
 
 pub struct StateStackPlugin;
 
 impl Plugin for StateStackPlugin {
     fn build(&self, app: &mut App) {
         app
+        .add_systems(Update, (
+            cancel_control,
+        ).run_if(
+            in_state(GameState::Dialog)
+            .or(in_state(GameState::Storage))
+            .or(in_state(GameState::Options))
+            .or(in_state(GameState::Pause))
+        ))
         .init_resource::<StateStack>()
         .register_type::<StateStack>();
     }
@@ -28,3 +38,43 @@ impl StateStack {
     }
 }
 
+
+/// Cancel backs out of whichever menu or dialog is open.
+fn cancel_control(
+    state: Res<State<GameState>>,
+    mut control_events: EventReader<GameControlEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+) {
+    let cancelled = control_events.read()
+        .filter(|e| e.just_pressed())
+        .any(|e| e.control == GameControl::Cancel);
+    if cancelled && !state.is_changed() {
+        next_state.set(state_stack.back());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestApp;
+
+    #[test]
+    fn cancel_backs_out_of_dialog() {
+        let mut app = TestApp::new();
+        app.enter(GameState::Dialog);
+        assert_eq!(app.state(), GameState::Dialog);
+
+        app.press(GameControl::Cancel);
+        app.tick(1);
+        assert_eq!(app.state(), GameState::Playing);
+    }
+
+    #[test]
+    fn cancel_does_nothing_while_playing() {
+        let mut app = TestApp::new();
+        app.press(GameControl::Cancel);
+        app.tick(1);
+        assert_eq!(app.state(), GameState::Playing);
+    }
+}
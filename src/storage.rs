@@ -0,0 +1,399 @@
+use std::cmp::Reverse;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::map::TriggerOnInteract;
+use crate::mob::TriggerEvent;
+use crate::party::DaemonInstance;
+use crate::party::Party;
+use crate::state_stack::StateStack;
+use crate::GameState;
+
+pub struct StoragePlugin;
+
+impl Plugin for StoragePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(Update, (
+            open_storage,
+        ).run_if(in_state(GameState::Playing)))
+        .add_systems(OnEnter(GameState::Storage), (
+            reset_storage_view,
+        ))
+        .add_systems(Update, (
+            storage_menu_control,
+        ).run_if(in_state(GameState::Storage)))
+        .init_resource::<DaemonStorage>()
+        .init_resource::<StorageView>()
+        .register_type::<DaemonStorage>()
+        .register_type::<StorageTerminal>();
+    }
+}
+
+/// Number of storage boxes.
+pub const BOX_COUNT: usize = 8;
+
+/// Daemons each box holds.
+pub const BOX_SLOTS: usize = 20;
+
+/// Opens the storage menu when interacted with, authored in Tiled.
+#[derive(Component, Default, Debug, Reflect)]
+#[reflect(Component, Default)]
+#[require(TriggerOnInteract)]
+pub struct StorageTerminal;
+
+#[derive(Reflect, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageSort {
+    #[default]
+    Species,
+    Level,
+    CaptureDate,
+}
+
+impl StorageSort {
+    pub fn next(self) -> Self {
+        match self {
+            StorageSort::Species => StorageSort::Level,
+            StorageSort::Level => StorageSort::CaptureDate,
+            StorageSort::CaptureDate => StorageSort::Species,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StorageSort::Species => "Species",
+            StorageSort::Level => "Level",
+            StorageSort::CaptureDate => "Caught",
+        }
+    }
+}
+
+#[derive(Reflect, Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct StorageBox {
+    pub name: String,
+    pub slots: Vec<Option<DaemonInstance>>,
+}
+
+impl StorageBox {
+    pub fn new(name: impl Into<String>) -> Self {
+        StorageBox {
+            name: name.into(),
+            slots: vec![None; BOX_SLOTS],
+        }
+    }
+
+    /// Sorts the daemons in this box and packs them into the first slots.
+    ///
+    /// Species sort alphabetically, levels highest first and capture dates oldest first.
+    pub fn sort(&mut self, by: StorageSort) {
+        let mut daemons: Vec<DaemonInstance> = self.slots.iter_mut()
+            .filter_map(Option::take)
+            .collect();
+        match by {
+            StorageSort::Species => daemons.sort_by(|a, b| a.species.cmp(&b.species)),
+            StorageSort::Level => daemons.sort_by_key(|daemon| Reverse(daemon.level)),
+            StorageSort::CaptureDate => daemons.sort_by_key(|daemon| daemon.caught_order),
+        }
+        for (slot, daemon) in self.slots.iter_mut().zip(daemons) {
+            *slot = Some(daemon);
+        }
+    }
+}
+
+/// A place a daemon can be moved from or to in the storage menu.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageSlot {
+    Party(usize),
+    /// A slot of the box currently being viewed.
+    Box(usize),
+}
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum StorageError {
+    #[error("There is no daemon there.")]
+    EmptySlot,
+    #[error("Your party is full.")]
+    PartyFull,
+    #[error("You need a daemon that can still fight.")]
+    LastHealthyMember,
+}
+
+/// Where daemons go when they don't fit in the party.
+#[derive(Resource, Reflect, Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[reflect(Resource)]
+pub struct DaemonStorage {
+    pub boxes: Vec<StorageBox>,
+    /// Daemons caught so far, used to stamp [`DaemonInstance::caught_order`].
+    captures: u32,
+}
+
+impl Default for DaemonStorage {
+    fn default() -> Self {
+        DaemonStorage {
+            boxes: (1..=BOX_COUNT)
+                .map(|number| StorageBox::new(format!("Box {}", number)))
+                .collect(),
+            captures: 0,
+        }
+    }
+}
+
+impl DaemonStorage {
+    /// Marks a newly caught daemon so storage can sort by capture date.
+    pub fn record_capture(&mut self, daemon: &mut DaemonInstance) {
+        self.captures += 1;
+        daemon.caught_order = self.captures;
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.boxes.iter().all(|storage_box| storage_box.slots.iter().all(Option::is_some))
+    }
+
+    /// Puts a daemon in the first free slot, returning its box index, or hands
+    /// it back if every box is full.
+    pub fn deposit(&mut self, daemon: DaemonInstance) -> Result<usize, DaemonInstance> {
+        for (index, storage_box) in self.boxes.iter_mut().enumerate() {
+            if let Some(slot) = storage_box.slots.iter_mut().find(|slot| slot.is_none()) {
+                *slot = Some(daemon);
+                return Ok(index);
+            }
+        }
+        Err(daemon)
+    }
+
+    /// Moves the daemon at `from` to `to` within box `box_index` and the party.
+    ///
+    /// An occupied destination swaps places; an empty one past the end of the
+    /// party adds to it. The party always keeps at least one daemon that can fight.
+    pub fn move_daemon(
+        &mut self,
+        party: &mut Party,
+        box_index: usize,
+        from: StorageSlot,
+        to: StorageSlot,
+    ) -> Result<(), StorageError> {
+        let slots = &mut self.boxes[box_index].slots;
+        match (from, to) {
+            (StorageSlot::Box(a), StorageSlot::Box(b)) => {
+                if slots[a].is_none() {
+                    return Err(StorageError::EmptySlot);
+                }
+                slots.swap(a, b);
+            }
+            (StorageSlot::Party(a), StorageSlot::Party(b)) => {
+                if a >= party.members().len() {
+                    return Err(StorageError::EmptySlot);
+                }
+                let b = b.min(party.members().len() - 1);
+                party.members_mut().swap(a, b);
+            }
+            (StorageSlot::Box(a), StorageSlot::Party(b)) => {
+                let daemon = slots[a].take().ok_or(StorageError::EmptySlot)?;
+                if b < party.members().len() {
+                    let swapped = party.replace(b, daemon);
+                    if !party.has_healthy_member() {
+                        slots[a] = Some(party.replace(b, swapped));
+                        return Err(StorageError::LastHealthyMember);
+                    }
+                    slots[a] = Some(swapped);
+                } else if let Err(daemon) = party.add(daemon) {
+                    slots[a] = Some(daemon);
+                    return Err(StorageError::PartyFull);
+                }
+            }
+            (StorageSlot::Party(a), StorageSlot::Box(b)) => {
+                if a >= party.members().len() {
+                    return Err(StorageError::EmptySlot);
+                }
+                match slots[b].take() {
+                    Some(daemon) => {
+                        let swapped = party.replace(a, daemon);
+                        if !party.has_healthy_member() {
+                            slots[b] = Some(party.replace(a, swapped));
+                            return Err(StorageError::LastHealthyMember);
+                        }
+                        slots[b] = Some(swapped);
+                    }
+                    None => {
+                        let daemon = party.remove(a);
+                        if !party.has_healthy_member() {
+                            party.insert(a, daemon);
+                            return Err(StorageError::LastHealthyMember);
+                        }
+                        slots[b] = Some(daemon);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// State of the storage menu while it is open.
+#[derive(Resource, Default, Debug)]
+pub struct StorageView {
+    pub current_box: usize,
+    /// Slot picked up with Interact, waiting for a destination.
+    pub held: Option<StorageSlot>,
+    pub sort: StorageSort,
+    /// Feedback shown at the bottom of the screen.
+    pub message: String,
+}
+
+/// Menu elements in the storage screen that aren't daemon slots.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageMenuAction {
+    PreviousBox,
+    NextBox,
+    Sort,
+    Exit,
+}
+
+fn open_storage(
+    terminal_query: Query<(), With<StorageTerminal>>,
+    mut events: EventReader<TriggerEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+) {
+    for event in events.read() {
+        if terminal_query.contains(event.triggered) {
+            next_state.set(state_stack.push(GameState::Storage));
+            return;
+        }
+    }
+}
+
+fn reset_storage_view(
+    mut view: ResMut<StorageView>,
+) {
+    view.held = None;
+    view.message = "Pick a daemon to move.".to_string();
+}
+
+fn storage_menu_control(
+    mut events: EventReader<TriggerEvent>,
+    slot_query: Query<&StorageSlot>,
+    action_query: Query<&StorageMenuAction>,
+    mut view: ResMut<StorageView>,
+    mut storage: ResMut<DaemonStorage>,
+    mut party: ResMut<Party>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+) {
+    for event in events.read() {
+        if let Ok(slot) = slot_query.get(event.triggered) {
+            let Some(held) = view.held.take() else {
+                view.held = Some(*slot);
+                view.message = "Move it where?".to_string();
+                continue;
+            };
+            let current_box = view.current_box;
+            view.message = match storage.move_daemon(&mut party, current_box, held, *slot) {
+                Ok(()) => "Pick a daemon to move.".to_string(),
+                Err(error) => error.to_string(),
+            };
+            continue;
+        }
+
+        let Ok(action) = action_query.get(event.triggered) else {
+            continue;
+        };
+        // Switching boxes drops whatever was held, since box slots are relative to the view.
+        view.held = None;
+        match action {
+            StorageMenuAction::PreviousBox => {
+                view.current_box = (view.current_box + BOX_COUNT - 1) % BOX_COUNT;
+            }
+            StorageMenuAction::NextBox => {
+                view.current_box = (view.current_box + 1) % BOX_COUNT;
+            }
+            StorageMenuAction::Sort => {
+                let current_box = view.current_box;
+                storage.boxes[current_box].sort(view.sort);
+                view.message = format!("Sorted by {}.", view.sort.label());
+                view.sort = view.sort.next();
+            }
+            StorageMenuAction::Exit => {
+                next_state.set(state_stack.back());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::party::StatSpread;
+    use crate::party::StatusCondition;
+
+    fn daemon(species: &str, level: u8, caught_order: u32) -> DaemonInstance {
+        DaemonInstance {
+            species: species.to_string(),
+            nickname: None,
+            level,
+            xp: 0,
+            hp: 10,
+            ivs: StatSpread::default(),
+            evs: StatSpread::default(),
+            moves: Vec::new(),
+            status: StatusCondition::Healthy,
+            caught_order,
+        }
+    }
+
+    fn box_species(storage_box: &StorageBox) -> Vec<Option<&str>> {
+        storage_box.slots.iter()
+            .take(4)
+            .map(|slot| slot.as_ref().map(|daemon| daemon.species.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn sorting_packs_slots() {
+        let mut storage_box = StorageBox::new("Box 1");
+        storage_box.slots[3] = Some(daemon("pebblit", 7, 1));
+        storage_box.slots[0] = Some(daemon("dewdrop", 3, 3));
+        storage_box.slots[5] = Some(daemon("mossling", 9, 2));
+
+        storage_box.sort(StorageSort::Species);
+        assert_eq!(box_species(&storage_box), vec![Some("dewdrop"), Some("mossling"), Some("pebblit"), None]);
+        storage_box.sort(StorageSort::Level);
+        assert_eq!(box_species(&storage_box), vec![Some("mossling"), Some("pebblit"), Some("dewdrop"), None]);
+        storage_box.sort(StorageSort::CaptureDate);
+        assert_eq!(box_species(&storage_box), vec![Some("pebblit"), Some("mossling"), Some("dewdrop"), None]);
+    }
+
+    #[test]
+    fn deposit_and_withdraw() {
+        let mut storage = DaemonStorage::default();
+        let mut party = Party::default();
+        party.add(daemon("emberkit", 5, 0)).unwrap();
+        party.add(daemon("mossling", 3, 1)).unwrap();
+
+        storage.move_daemon(&mut party, 0, StorageSlot::Party(1), StorageSlot::Box(4)).unwrap();
+        assert_eq!(party.members().len(), 1);
+        assert_eq!(storage.boxes[0].slots[4].as_ref().unwrap().species, "mossling");
+
+        assert_eq!(
+            storage.move_daemon(&mut party, 0, StorageSlot::Party(0), StorageSlot::Box(0)),
+            Err(StorageError::LastHealthyMember),
+        );
+        assert_eq!(party.members().len(), 1);
+
+        storage.move_daemon(&mut party, 0, StorageSlot::Box(4), StorageSlot::Party(5)).unwrap();
+        assert_eq!(party.members()[1].species, "mossling");
+        assert!(storage.boxes[0].slots[4].is_none());
+    }
+
+    #[test]
+    fn deposit_fills_the_next_box() {
+        let mut storage = DaemonStorage::default();
+        for _ in 0..BOX_SLOTS {
+            storage.deposit(daemon("pebblit", 2, 0)).unwrap();
+        }
+        assert_eq!(storage.deposit(daemon("dewdrop", 2, 0)), Ok(1));
+    }
+}
@@ -0,0 +1,30 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+use serde::Serialize;
+
+pub struct StoryFlagsPlugin;
+
+impl Plugin for StoryFlagsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<StoryFlags>()
+        .register_type::<StoryFlags>();
+    }
+}
+
+/// Named one-way switches recording what the player has done, e.g. which
+/// trainers they have beaten.
+#[derive(Resource, Reflect, Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StoryFlags(HashSet<String>);
+
+impl StoryFlags {
+    pub fn set(&mut self, flag: impl Into<String>) {
+        self.0.insert(flag.into());
+    }
+
+    pub fn is_set(&self, flag: &str) -> bool {
+        self.0.contains(flag)
+    }
+}
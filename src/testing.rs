@@ -0,0 +1,151 @@
+//! Headless app for testing gameplay systems: grid movement, triggers and
+//! state changes, without rendering, assets or maps.
+
+use std::time::Duration;
+
+use bevy::input::InputPlugin;
+use bevy::prelude::*;
+use bevy::state::app::StatesPlugin;
+use bevy::time::TimeUpdateStrategy;
+
+use crate::control::map_inputs_to_control_events;
+use crate::control::ControlPlugin;
+use crate::control::ControlStatus;
+use crate::control::GameControl;
+use crate::control::GameControlEvent;
+use crate::graph::grid_transform::GridTransform;
+use crate::map::index_grid_positions;
+use crate::map::GridIndex;
+use crate::mob::GridPosition;
+use crate::mob::MobPlugin;
+use crate::mob::TriggerEvent;
+use crate::mob::TriggerOnMoveOntoEvent;
+use crate::player::player_interact_control;
+use crate::player::player_move_control;
+use crate::player::Player;
+use crate::state_stack::StateStack;
+use crate::state_stack::StateStackPlugin;
+use crate::trainer::TrainerEncounter;
+use crate::GameState;
+
+/// Time each [`TestApp::tick`] advances by.
+pub const TICK: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Ticks a step takes at walking pace, rounded up. Holding a direction this
+/// long walks exactly one tile.
+pub const STEP_TICKS: usize = 25;
+
+/// Triggers fired since the app was built, as `(triggering, triggered)`.
+#[derive(Resource, Default, Debug)]
+pub struct FiredTriggers {
+    pub triggered: Vec<(Entity, Entity)>,
+    pub moved_onto: Vec<(Entity, Entity)>,
+}
+
+pub struct TestApp {
+    pub app: App,
+}
+
+impl TestApp {
+    /// An app in [`GameState::Playing`] with controls, mobs and the grid index.
+    pub fn new() -> Self {
+        let mut app = App::new();
+        app
+        .add_plugins((
+            MinimalPlugins,
+            StatesPlugin,
+            InputPlugin,
+            ControlPlugin,
+            MobPlugin,
+            StateStackPlugin,
+        ))
+        .insert_resource(TimeUpdateStrategy::ManualDuration(TICK))
+        .insert_state(GameState::Playing)
+        .init_resource::<GridIndex>()
+        .init_resource::<TrainerEncounter>()
+        .init_resource::<FiredTriggers>()
+        .add_event::<TriggerEvent>()
+        .add_systems(Update, (
+            index_grid_positions,
+        ))
+        .add_systems(Update, (
+            player_move_control.after(map_inputs_to_control_events),
+            player_interact_control.after(map_inputs_to_control_events),
+        ).run_if(in_state(GameState::Playing)))
+        .add_systems(Last, record_triggers);
+        app.world_mut().resource_mut::<StateStack>().push(GameState::Playing);
+        app.update();
+        TestApp { app }
+    }
+
+    /// Spawns `bundle` standing on `position`.
+    pub fn spawn_at(&mut self, position: GridTransform, bundle: impl Bundle) -> Entity {
+        let transform: Transform = position.into();
+        let entity = self.app.world_mut()
+            .spawn((bundle, transform, GridPosition(position)))
+            .id();
+        self.app.world_mut().resource_mut::<GridIndex>().update(entity, position);
+        entity
+    }
+
+    pub fn spawn_player(&mut self, position: GridTransform) -> Entity {
+        self.spawn_at(position, Player)
+    }
+
+    pub fn tick(&mut self, ticks: usize) {
+        for _ in 0..ticks {
+            self.app.update();
+        }
+    }
+
+    /// Taps `control` for a single tick.
+    pub fn press(&mut self, control: GameControl) {
+        for status in [ControlStatus::JustPressed, ControlStatus::Pressed] {
+            self.app.world_mut().send_event(GameControlEvent { control, status });
+        }
+        self.tick(1);
+    }
+
+    /// Holds `control` down for `ticks` ticks.
+    pub fn hold(&mut self, control: GameControl, ticks: usize) {
+        self.press(control);
+        for _ in 1..ticks {
+            self.app.world_mut().send_event(GameControlEvent { control, status: ControlStatus::Pressed });
+            self.tick(1);
+        }
+    }
+
+    /// Pushes `state` the way opening a menu or dialog would.
+    pub fn enter(&mut self, state: GameState) {
+        let state = self.app.world_mut().resource_mut::<StateStack>().push(state);
+        self.app.world_mut().resource_mut::<NextState<GameState>>().set(state);
+        self.tick(1);
+    }
+
+    pub fn position(&self, entity: Entity) -> GridTransform {
+        self.app.world().get::<GridPosition>(entity)
+            .expect("Entity has no grid position.")
+            .0
+    }
+
+    pub fn state(&self) -> GameState {
+        self.app.world().resource::<State<GameState>>().get().clone()
+    }
+
+    pub fn triggers(&self) -> &FiredTriggers {
+        self.app.world().resource::<FiredTriggers>()
+    }
+}
+
+fn record_triggers(
+    mut fired: ResMut<FiredTriggers>,
+    mut trigger_events: EventReader<TriggerEvent>,
+    mut moved_onto_events: EventReader<TriggerOnMoveOntoEvent>,
+) {
+    for event in trigger_events.read() {
+        fired.triggered.push((event.triggering, event.triggered));
+    }
+    for event in moved_onto_events.read() {
+        fired.moved_onto.push((event.moved, event.triggered));
+    }
+}
@@ -0,0 +1,246 @@
+//! Wrapping dialog text to the width of the dialog box, and splitting pages
+//! with more lines than the box holds into continuation pages.
+
+use bevy::prelude::*;
+use bevy::text::cosmic_text::ttf_parser::Face;
+
+use crate::text_loading::Dialog;
+use crate::text_loading::Page;
+use crate::text_loading::Span;
+
+/// Line height bevy lays text out with, relative to the font size.
+const LINE_HEIGHT: f32 = 1.2;
+
+/// Room a dialog page has for its text.
+#[derive(Debug, Clone, Copy)]
+pub struct PageLayout {
+    pub width: f32,
+    /// Width left beside the choice menu on choice pages.
+    pub choice_width: f32,
+    pub lines: usize,
+}
+
+/// Measures text with the advances from the dialog font files.
+pub struct TextMeasure<'a> {
+    regular: Face<'a>,
+    bold: Face<'a>,
+    font_size: f32,
+}
+
+impl<'a> TextMeasure<'a> {
+    pub fn new(regular: &'a Font, bold: &'a Font, font_size: f32) -> Option<Self> {
+        Some(TextMeasure {
+            regular: Face::parse(&regular.data, 0).ok()?,
+            bold: Face::parse(&bold.data, 0).ok()?,
+            font_size,
+        })
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.font_size * LINE_HEIGHT
+    }
+
+    /// Width of `text` in the font of `span`.
+    pub fn width(&self, span: &Span, text: &str) -> f32 {
+        let face = if span.bold { &self.bold } else { &self.regular };
+        let scale = self.font_size / face.units_per_em() as f32;
+        text.chars()
+            .filter_map(|c| face.glyph_index(c))
+            .filter_map(|glyph| face.glyph_hor_advance(glyph))
+            .map(|advance| advance as f32 * scale)
+            .sum()
+    }
+}
+
+/// Part of a line, as the index of the span it comes from and its text.
+pub type Fragment = (usize, String);
+
+fn push_char(fragments: &mut Vec<Fragment>, span: usize, c: char) {
+    match fragments.last_mut() {
+        Some((last, text)) if *last == span => text.push(c),
+        _ => fragments.push((span, c.to_string())),
+    }
+}
+
+/// Breaks `spans` into lines no wider than `width`, at spaces and at the
+/// newlines already in the text. Spaces inside a `{...}` placeholder don't
+/// break lines. A word wider than a whole line overflows it.
+pub fn wrap_lines(
+    spans: &[Span],
+    width: f32,
+    measure: impl Fn(&Span, &str) -> f32,
+) -> Vec<Vec<Fragment>> {
+    let fragments_width = |fragments: &[Fragment]| -> f32 {
+        fragments.iter().map(|(span, text)| measure(&spans[*span], text)).sum()
+    };
+
+    let mut lines: Vec<Vec<Fragment>> = vec![Vec::new()];
+    let mut line_width = 0.;
+    // Spaces since the last word, dropped if the line breaks after them
+    let mut spaces = Vec::new();
+    let mut word = Vec::new();
+    let mut in_placeholder = false;
+
+    let place_word = |lines: &mut Vec<Vec<Fragment>>, line_width: &mut f32, spaces: &mut Vec<Fragment>, word: &mut Vec<Fragment>| {
+        if word.is_empty() {
+            return;
+        }
+        let word_width = fragments_width(word);
+        let spaces_width = fragments_width(spaces);
+        let line = lines.last_mut().unwrap();
+        if !line.is_empty() && *line_width + spaces_width + word_width > width {
+            lines.push(Vec::new());
+            *line_width = 0.;
+        } else {
+            line.append(spaces);
+            *line_width += spaces_width;
+        }
+        spaces.clear();
+        let line = lines.last_mut().unwrap();
+        for (span, text) in word.drain(..) {
+            for c in text.chars() {
+                push_char(line, span, c);
+            }
+        }
+        *line_width += word_width;
+    };
+
+    for (index, span) in spans.iter().enumerate() {
+        for c in span.text.chars() {
+            if c == '{' || c == '}' {
+                in_placeholder = c == '{';
+            }
+            if c == '\n' {
+                place_word(&mut lines, &mut line_width, &mut spaces, &mut word);
+                spaces.clear();
+                lines.push(Vec::new());
+                line_width = 0.;
+            } else if c.is_whitespace() && !in_placeholder {
+                place_word(&mut lines, &mut line_width, &mut spaces, &mut word);
+                push_char(&mut spaces, index, c);
+            } else {
+                push_char(&mut word, index, c);
+            }
+        }
+    }
+    place_word(&mut lines, &mut line_width, &mut spaces, &mut word);
+    lines
+}
+
+/// Puts `lines` back together as spans styled like the ones they came from.
+/// `started` tracks which spans have been shown already, so a span split over
+/// several pages only pauses before its first part.
+fn join_lines(spans: &[Span], lines: &[Vec<Fragment>], started: &mut [bool]) -> Vec<Span> {
+    let mut joined: Vec<(usize, Span)> = Vec::new();
+    for (line_index, line) in lines.iter().enumerate() {
+        if line_index > 0 {
+            let span = line.first().map_or(0, |(span, _)| *span);
+            match joined.last_mut() {
+                Some((_, last)) => last.text.push('\n'),
+                None => joined.push((span, Span {
+                    text: "\n".to_string(),
+                    pausebefore: 0,
+                    ..spans[span].clone()
+                })),
+            }
+        }
+        for (span, text) in line {
+            match joined.last_mut() {
+                Some((last, joined_span)) if last == span => joined_span.text.push_str(text),
+                _ => {
+                    let pausebefore = if started[*span] { 0 } else { spans[*span].pausebefore };
+                    started[*span] = true;
+                    joined.push((*span, Span {
+                        text: text.clone(),
+                        pausebefore,
+                        ..spans[*span].clone()
+                    }));
+                }
+            }
+        }
+    }
+    joined.into_iter().map(|(_, span)| span).collect()
+}
+
+/// Wraps the text of every page in `dialog`, and splits pages that take more
+/// lines than `layout` allows. The parts before the last are marked as
+/// [`Page::continued`]; labels stay on the first part, and choices and where
+/// to go next on the last.
+pub fn paginate(dialog: &Dialog, layout: PageLayout, measure: impl Fn(&Span, &str) -> f32) -> Dialog {
+    let mut pages = Vec::new();
+    for page in dialog.iter() {
+        let width = if page.choices.is_empty() { layout.width } else { layout.choice_width };
+        let lines = wrap_lines(&page.spans, width, &measure);
+        let parts: Vec<&[Vec<Fragment>]> = lines.chunks(layout.lines.max(1)).collect();
+        let mut started = vec![false; page.spans.len()];
+        for (index, part) in parts.iter().enumerate() {
+            let first = index == 0;
+            let last = index == parts.len() - 1;
+            pages.push(Page {
+                speaker: page.speaker.clone(),
+                mood: page.mood.clone(),
+                spans: join_lines(&page.spans, part, &mut started),
+                label: page.label.clone().filter(|_| first),
+                next: page.next.clone().filter(|_| last),
+                end: page.end && last,
+                choices: if last { page.choices.clone() } else { Vec::new() },
+                continued: !last,
+            });
+        }
+    }
+    Dialog(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every character is 5 pixels wide.
+    fn measure(_span: &Span, text: &str) -> f32 {
+        text.chars().count() as f32 * 5.
+    }
+
+    fn page_text(page: &Page) -> String {
+        page.spans.iter().map(|span| span.text.as_str()).collect()
+    }
+
+    #[test]
+    fn wraps_at_spaces_across_spans() {
+        let spans: Vec<Span> = ron::de::from_str(r#"[
+            Span(text: "one two "),
+            Span(text: "three", bold: true),
+            Span(text: " four\nfive"),
+        ]"#).unwrap();
+        let lines = wrap_lines(&spans, 40., measure);
+        let texts: Vec<String> = lines.iter()
+            .map(|line| line.iter().map(|(_, text)| text.as_str()).collect())
+            .collect();
+        assert_eq!(texts, ["one two", "three", "four", "five"]);
+        assert_eq!(lines[1], [(1, "three".to_string())]);
+
+        let spans: Vec<Span> = ron::de::from_str(r#"[Span(text: "{a|# b|# c} d")]"#).unwrap();
+        assert_eq!(wrap_lines(&spans, 40., measure)[0], [(0, "{a|# b|# c}".to_string())]);
+    }
+
+    #[test]
+    fn long_pages_continue_on_the_next() {
+        let dialog: Dialog = ron::de::from_str(r#"Dialog([
+            Page(label: Some("start"), speaker: "sign", mood: "neutral", spans: [
+                Span(text: "aaa bbb ccc ", pausebefore: 100),
+                Span(text: "ddd", pausebefore: 200, effect: Wave),
+            ], choices: [Choice(text: "Ok")]),
+        ])"#).unwrap();
+        let layout = PageLayout { width: 100., choice_width: 20., lines: 3 };
+        let pages = paginate(&dialog, layout, measure);
+
+        assert_eq!(pages.len(), 2);
+        assert_eq!(page_text(&pages[0]), "aaa\nbbb\nccc");
+        assert_eq!(page_text(&pages[1]), "ddd");
+        assert!(pages[0].continued && !pages[1].continued);
+        assert_eq!(pages[0].label.as_deref(), Some("start"));
+        assert!(pages[0].choices.is_empty());
+        assert_eq!(pages[1].choices.len(), 1);
+        assert_eq!(pages[1].spans[0].pausebefore, 200);
+        assert_eq!(pages[1].spans[0].effect, crate::text_loading::SpanEffect::Wave);
+    }
+}
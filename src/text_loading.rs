@@ -1,9 +1,16 @@
+use crate::persist;
+use crate::persist::Persistence;
+use crate::text_variables::PluralRule;
 use crate::GameState;
+use bevy::asset::LoadState;
 use bevy::prelude::*;
 use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::Path;
 use sys_locale::get_locale;
 use thiserror::Error;
-use bevy::asset::{io::Reader, AssetLoader, LoadContext};
+use bevy::asset::{io::Reader, AssetLoader, LoadContext, ReadAssetBytesError};
 
 pub struct TextLoadingPlugin;
 
@@ -11,23 +18,64 @@ impl Plugin for TextLoadingPlugin {
     fn build(&self, app: &mut App) {
         app
         .add_systems(Update, (
-            spawn_game_text_resource_and_advance_state,
-        ).run_if(in_state(GameState::TextLoading)))
+            load_language,
+            spawn_game_text_resource_and_advance_state
+                .run_if(resource_exists::<LoadingLocales>),
+        ).chain())
         .init_asset::<GameText>()
         .init_asset_loader::<GameTextAssetLoader>()
-        .init_resource::<TempGameTextHandle>();
+        .init_resource::<Language>();
     }
 }
 
+/// Loads the text of the language whenever it changes, including at startup.
+fn load_language(
+    mut commands: Commands,
+    language: Res<Language>,
+    asset_server: Res<AssetServer>,
+) {
+    if language.is_changed() {
+        info!("Loading text for {}", **language);
+        commands.insert_resource(LoadingLocales::new(&asset_server, &language));
+    }
+}
+
+/// Once every locale in the fallback chain has loaded or failed to, layers
+/// them into the [`GameText`] resource with the default locale at the bottom,
+/// so whatever a locale doesn't translate falls back to the next one.
 fn spawn_game_text_resource_and_advance_state (
     mut commands: Commands, 
+    state: Res<State<GameState>>,
     mut next_state: ResMut<NextState<GameState>>,
     mut game_text_server: ResMut<Assets<GameText>>,
-    game_text_handle: Res<TempGameTextHandle>,
+    asset_server: Res<AssetServer>,
+    loading: Res<LoadingLocales>,
 ) {
-    if let Some(game_text) = game_text_server.remove(&**game_text_handle) {
-        commands.insert_resource(game_text);
-        commands.remove_resource::<TempGameTextHandle>();
+    let finished = loading.iter().all(|(_, handle)| !matches!(
+        asset_server.get_load_state(handle),
+        Some(LoadState::Loading | LoadState::NotLoaded),
+    ));
+    if !finished {
+        return;
+    }
+
+    let mut game_text = GameText::default();
+    let mut found = false;
+    for (locale, handle) in loading.iter().rev() {
+        match game_text_server.remove(handle) {
+            Some(text) => {
+                game_text.overlay(text);
+                found = true;
+            }
+            None => info!("No text for locale {}", locale),
+        }
+    }
+    if !found {
+        error!("No locale could be loaded, text will be missing");
+    }
+    commands.insert_resource(game_text);
+    commands.remove_resource::<LoadingLocales>();
+    if *state.get() == GameState::TextLoading {
         next_state.set(GameState::AssetLoading);
     }
 }
@@ -36,20 +84,168 @@ fn spawn_game_text_resource_and_advance_state (
 fn default_speed() -> f32 { 1.0 }
 fn default_zero() -> u64 { 0 }
 
-#[derive(Resource, Reflect, Asset, Debug, Deserialize)]
+/// All text of a locale, keyed by the names maps and code refer to it by.
+#[derive(Resource, Reflect, Asset, Debug, Default)]
 pub struct GameText {
-    pub string_test: String,
-    pub dialog_test: Dialog,
+    /// How this language picks plural forms in dialog placeholders.
+    pub plural_rule: PluralRule,
+    pub strings: HashMap<String, String>,
+    pub dialogs: HashMap<String, Dialog>,
+}
+
+impl GameText {
+    /// Adds the entries of `table`, which was read from `file`. Keys that an
+    /// earlier file already had are replaced with a warning.
+    pub fn merge(&mut self, file: &str, table: TextTable) {
+        for (key, string) in table.strings {
+            if self.strings.insert(key.clone(), string).is_some() {
+                warn!("String `{}` in {} replaces an earlier one", key, file);
+            }
+        }
+        for (key, dialog) in table.dialogs {
+            if self.dialogs.insert(key.clone(), dialog).is_some() {
+                warn!("Dialog `{}` in {} replaces an earlier one", key, file);
+            }
+        }
+    }
+
+    pub fn dialog(&self, key: &str) -> Option<&Dialog> {
+        self.dialogs.get(key)
+    }
+
+    /// Takes the plural rule and every entry of `text`, keeping the entries
+    /// it doesn't have.
+    pub fn overlay(&mut self, text: GameText) {
+        self.plural_rule = text.plural_rule;
+        self.strings.extend(text.strings);
+        self.dialogs.extend(text.dialogs);
+    }
+
+    /// The dialog for `key`, or a page naming the missing key so the game
+    /// carries on.
+    pub fn dialog_or_placeholder(&self, key: &str) -> Dialog {
+        match self.dialog(key) {
+            Some(dialog) => dialog.clone(),
+            None => {
+                warn!("Unknown dialog `{}`", key);
+                Dialog::placeholder(key)
+            }
+        }
+    }
+}
+
+/// One file of a locale's text, e.g. the dialogs of one map.
+#[derive(Debug, Deserialize, Default)]
+pub struct TextTable {
+    #[serde(default)]
+    pub strings: HashMap<String, String>,
+    #[serde(default)]
+    pub dialogs: HashMap<String, Dialog>,
+}
+
+/// The file a locale is loaded from, listing the [`TextTable`] files its
+/// text is split over, relative to itself.
+#[derive(Debug, Deserialize)]
+struct Locale {
+    #[serde(default)]
+    plural_rule: PluralRule,
+    files: Vec<String>,
 }
 
 #[derive(Debug, Reflect, Deserialize, Deref, DerefMut, Clone)]
-pub struct Dialog(Vec<Page>);
+pub struct Dialog(pub Vec<Page>);
+
+impl Dialog {
+    fn placeholder(key: &str) -> Self {
+        Dialog(vec![Page {
+            speaker: String::new(),
+            mood: String::new(),
+            spans: vec![Span {
+                text: format!("[missing dialog: {}]", key),
+                speed: default_speed(),
+                pausebefore: 0,
+                color: None,
+                bold: false,
+                effect: SpanEffect::None,
+            }],
+            label: None,
+            next: None,
+            end: false,
+            choices: Vec::new(),
+            continued: false,
+        }])
+    }
+
+    pub fn page_labeled(&self, label: &str) -> Option<usize> {
+        let index = self.iter().position(|page| page.label.as_deref() == Some(label));
+        if index.is_none() {
+            warn!("Dialog has no page labeled {}", label);
+        }
+        index
+    }
+
+    /// The page to show once page `index` is done, or `None` if the dialog ends.
+    pub fn page_after(&self, index: usize) -> Option<usize> {
+        let page = &self[index];
+        if page.end {
+            return None;
+        }
+        match &page.next {
+            Some(label) => self.page_labeled(label),
+            None => Some(index + 1).filter(|next| *next < self.len()),
+        }
+    }
+
+    /// The page to show once `choice` is picked on page `index`.
+    pub fn page_after_choice(&self, index: usize, choice: &Choice) -> Option<usize> {
+        match &choice.goto {
+            Some(label) => self.page_labeled(label),
+            None => self.page_after(index),
+        }
+    }
+}
 
 #[derive(Debug, Reflect, Deserialize, Clone)]
 pub struct Page {
     pub speaker: String,
     pub mood: String,
     pub spans: Vec<Span>,
+
+    /// Name for choices and other pages to jump to.
+    #[serde(default)]
+    pub label: Option<String>,
+
+    /// Label of the page to continue at, instead of the following one.
+    #[serde(default)]
+    pub next: Option<String>,
+
+    /// Closes the dialog after this page.
+    #[serde(default)]
+    pub end: bool,
+
+    /// Options offered once the text is revealed, at most three.
+    #[serde(default)]
+    pub choices: Vec<Choice>,
+
+    /// Set on pages [`crate::text_layout::paginate`] split off from a page
+    /// too long for the dialog box, whose text carries on in the next page.
+    #[serde(skip)]
+    pub continued: bool,
+}
+
+/// An answer on a choice page.
+#[derive(Debug, Reflect, Deserialize, Clone)]
+pub struct Choice {
+    pub text: String,
+
+    /// Label of the page to continue at. Without one the dialog carries on as
+    /// after any other page.
+    #[serde(default)]
+    pub goto: Option<String>,
+
+    /// Sent as a [`crate::dialog::DialogOutcomeEvent`] when picked.
+    #[serde(default)]
+    pub outcome: Option<String>,
 }
 
 #[derive(Debug, Reflect, Deserialize, Clone)]
@@ -62,17 +258,116 @@ pub struct Span {
     // A pause before displaying this span, in milliseconds
     #[serde(default = "default_zero")]
     pub pausebefore: u64,
+
+    /// Red, green and blue of the text, instead of the dialog color.
+    #[serde(default)]
+    pub color: Option<(u8, u8, u8)>,
+
+    #[serde(default)]
+    pub bold: bool,
+
+    #[serde(default)]
+    pub effect: SpanEffect,
+}
+
+/// Movement applied to each letter of a span.
+#[derive(Debug, Reflect, Deserialize, Clone, Copy, Default, PartialEq)]
+pub enum SpanEffect {
+    #[default]
+    None,
+    /// Letters jitter in place.
+    Shake,
+    /// Letters bob up and down one after another.
+    Wave,
+}
+
+/// Locale anything untranslated falls back to.
+pub const DEFAULT_LOCALE: &str = "en-US";
+
+/// Locales the language option offers, with their names.
+pub const LANGUAGES: [(&str, &str); 2] = [
+    ("en-US", "English"),
+    ("fr", "Français"),
+];
+
+const LANGUAGE_NAME: &str = "language";
+
+/// Locales to look for text in, most specific first: `fr-CA` falls back to
+/// `fr`, then to [`DEFAULT_LOCALE`].
+pub fn locale_chain(locale: &str) -> Vec<String> {
+    let locale = locale.replace('_', "-");
+    let mut chain = vec![locale.clone()];
+    if let Some((language, _)) = locale.split_once('-') {
+        chain.push(language.to_string());
+    }
+    if !chain.iter().any(|locale| locale == DEFAULT_LOCALE) {
+        chain.push(DEFAULT_LOCALE.to_string());
+    }
+    chain
+}
+
+/// The locale text is shown in: the one picked in the options, or the
+/// system's.
+#[derive(Resource, Debug, Clone, PartialEq, Eq, Deref, Serialize, Deserialize)]
+pub struct Language(String);
+
+impl Default for Language {
+    fn default() -> Self {
+        let saved = match persist::read(LANGUAGE_NAME) {
+            Ok(json) => json.and_then(|json| serde_json::from_str(&json).ok()),
+            Err(error) => {
+                warn!("Could not read language: {}", error);
+                None
+            }
+        };
+        Language(saved
+            .or_else(get_locale)
+            .unwrap_or_else(|| DEFAULT_LOCALE.to_string()))
+    }
+}
+
+impl Language {
+    /// Position in [`LANGUAGES`], matching `fr-CA` to `fr`.
+    fn offered_index(&self) -> Option<usize> {
+        locale_chain(&self.0).iter()
+            .find_map(|locale| LANGUAGES.iter().position(|(offered, _)| offered == locale))
+    }
+
+    pub fn name(&self) -> &str {
+        self.offered_index().map_or(&self.0, |index| LANGUAGES[index].1)
+    }
+
+    /// The language offered after this one.
+    pub fn next(&self) -> Language {
+        let index = self.offered_index().map_or(0, |index| (index + 1) % LANGUAGES.len());
+        Language(LANGUAGES[index].0.to_string())
+    }
+
+    /// Writes the language so it is used on the next start.
+    pub fn save(&self, persistence: &mut Persistence) {
+        let result = serde_json::to_string(&self.0)
+            .map_err(|error| error.to_string())
+            .and_then(|json| persistence.write(LANGUAGE_NAME, &json).map_err(|error| error.to_string()));
+        if let Err(error) = result {
+            error!("Could not save language: {}", error);
+        }
+    }
 }
 
-#[derive(Resource, Deref, DerefMut)]
-pub struct TempGameTextHandle(Handle<GameText>);
+/// The fallback chain of the language being loaded, leaving out locales
+/// the game doesn't ship.
+#[derive(Resource, Deref)]
+pub struct LoadingLocales(Vec<(String, Handle<GameText>)>);
 
-impl FromWorld for TempGameTextHandle {
-    fn from_world(world: &mut World) -> Self {
-        let locale = get_locale().unwrap_or_else(|| String::from("en-US"));
-        let path = format!("locales/{}.ron", locale);
-        let asset_server = world.resource::<AssetServer>();
-        TempGameTextHandle(asset_server.load(path))
+impl LoadingLocales {
+    fn new(asset_server: &AssetServer, language: &Language) -> Self {
+        LoadingLocales(locale_chain(language).into_iter()
+            .filter(|locale| LANGUAGES.iter().any(|(offered, _)| offered == locale))
+            .map(|locale| {
+                let handle = asset_server.load(format!("locales/{}.ron", locale));
+                (locale, handle)
+            })
+            .collect())
     }
 }
 
@@ -82,13 +377,22 @@ struct GameTextAssetLoader;
 /// Possible errors that can be produced by [`CustomAssetLoader`]
 #[non_exhaustive]
 #[derive(Debug, Error)]
-enum GameTextAssetLoaderError {
+pub enum GameTextAssetLoaderError {
     /// An [IO](std::io) Error
     #[error("Could not load asset: {0}")]
     Io(#[from] std::io::Error),
     /// A [RON](ron) Error
     #[error("Could not parse RON: {0}")]
     RonSpannedError(#[from] ron::error::SpannedError),
+    /// A text file listed by the locale could not be read
+    #[error("Could not read text file: {0}")]
+    ReadTextFile(#[from] ReadAssetBytesError),
+    /// A [RON](ron) Error in a text file listed by the locale
+    #[error("Could not parse {file}: {source}")]
+    TextFileRon {
+        file: String,
+        source: ron::error::SpannedError,
+    },
 }
 
 impl AssetLoader for GameTextAssetLoader {
@@ -100,12 +404,24 @@ impl AssetLoader for GameTextAssetLoader {
         &self,
         reader: &mut dyn Reader,
         _settings: &Self::Settings,
-        _load_context: &mut LoadContext<'_>,
+        load_context: &mut LoadContext<'_>,
     ) -> Result<Self::Asset, Self::Error> {
         let mut bytes = Vec::new();
         reader.read_to_end(&mut bytes).await?;
-        let custom_asset = ron::de::from_bytes::<GameText>(&bytes)?;
-        Ok(custom_asset)
+        let locale = ron::de::from_bytes::<Locale>(&bytes)?;
+
+        let directory = load_context.path().parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut game_text = GameText {
+            plural_rule: locale.plural_rule,
+            ..default()
+        };
+        for file in locale.files {
+            let bytes = load_context.read_asset_bytes(directory.join(&file)).await?;
+            let table = ron::de::from_bytes::<TextTable>(&bytes)
+                .map_err(|source| GameTextAssetLoaderError::TextFileRon { file: file.clone(), source })?;
+            game_text.merge(&file, table);
+        }
+        Ok(game_text)
     }
 
     fn extensions(&self) -> &[&str] {
@@ -113,4 +429,80 @@ impl AssetLoader for GameTextAssetLoader {
     }
 }
 
+/// Reads `locale` straight from the `locales` folder, the way
+/// [`GameTextAssetLoader`] does, for checking text outside the game.
+pub fn load_locale(locales: &Path, locale: &str) -> Result<GameText, GameTextAssetLoaderError> {
+    let manifest: Locale = ron::de::from_bytes(&std::fs::read(locales.join(format!("{}.ron", locale)))?)?;
+    let mut game_text = GameText {
+        plural_rule: manifest.plural_rule,
+        ..default()
+    };
+    for file in manifest.files {
+        let table = ron::de::from_bytes(&std::fs::read(locales.join(&file))?)
+            .map_err(|source| GameTextAssetLoaderError::TextFileRon { file: file.clone(), source })?;
+        game_text.merge(&file, table);
+    }
+    Ok(game_text)
+}
 
+#[cfg(test)]
+pub fn read_locale(locale: &str) -> GameText {
+    load_locale(&Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/locales"), locale).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUESTION: &str = r#"Dialog([
+        Page(speaker: "sign", mood: "neutral", spans: [Span(text: "Rest?")], choices: [
+            Choice(text: "Yes", goto: Some("yes"), outcome: Some("heal_party")),
+            Choice(text: "No"),
+        ]),
+        Page(speaker: "sign", mood: "neutral", spans: [Span(text: "Maybe later.")], end: true),
+        Page(label: Some("yes"), speaker: "sign", mood: "neutral", spans: [Span(text: "Rested.")], next: Some("bye")),
+        Page(speaker: "sign", mood: "neutral", spans: [Span(text: "Skipped.")]),
+        Page(label: Some("bye"), speaker: "sign", mood: "neutral", spans: [Span(text: "Bye!")]),
+    ])"#;
+
+    #[test]
+    fn locale_parses() {
+        let game_text = read_locale("en-US");
+        let rest_offer = game_text.dialog("rest_offer").unwrap();
+        assert_eq!(rest_offer.page_after_choice(0, &rest_offer[0].choices[0]), Some(1));
+    }
+
+    #[test]
+    fn untranslated_text_falls_back() {
+        assert_eq!(locale_chain("fr_CA"), ["fr-CA", "fr", "en-US"]);
+        assert_eq!(locale_chain("en-US"), ["en-US", "en"]);
+
+        let mut game_text = read_locale(DEFAULT_LOCALE);
+        game_text.overlay(read_locale("fr"));
+        assert_eq!(game_text.plural_rule, PluralRule::ZeroOneOther);
+        assert_eq!(game_text.dialog("game_saved").unwrap()[0].spans[0].text, "Partie sauvegardée.");
+        assert!(game_text.dialog("hiker_challenge").is_some());
+    }
+
+    #[test]
+    fn missing_dialogs_get_a_placeholder() {
+        let game_text = read_locale("en-US");
+        let dialog = game_text.dialog_or_placeholder("no_such_dialog");
+        assert_eq!(dialog.len(), 1);
+        assert_eq!(dialog[0].spans[0].text, "[missing dialog: no_such_dialog]");
+    }
+
+    #[test]
+    fn choices_branch_to_labels() {
+        let dialog: Dialog = ron::de::from_str(QUESTION).unwrap();
+        let choices = &dialog[0].choices;
+        assert_eq!(choices[0].outcome.as_deref(), Some("heal_party"));
+
+        assert_eq!(dialog.page_after_choice(0, &choices[0]), Some(2));
+        assert_eq!(dialog.page_after(2), Some(4));
+        assert_eq!(dialog.page_after(4), None);
+
+        assert_eq!(dialog.page_after_choice(0, &choices[1]), Some(1));
+        assert_eq!(dialog.page_after(1), None);
+    }
+}
@@ -0,0 +1,256 @@
+//! Placeholders in dialog text, filled in from the game state when a page is
+//! shown.
+//!
+//! `{player_name}` is the player's name, `{daemon:0.nickname}` the name of the
+//! first party member (also `.species` and `.level`), and `{item_count:potion}`
+//! how many of an item the player carries. Forms after `|` are picked by the
+//! locale's [`PluralRule`] for the value, with `#` standing for the value:
+//! `{item_count:potion|# potion|# potions}`. `{{` and `}}` are literal braces.
+//!
+//! Placeholders that can't be filled in are left in the text as written, so
+//! they are easy to spot.
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::inventory::Inventory;
+use crate::party::Party;
+use crate::species::SpeciesRegistry;
+
+pub struct TextVariablesPlugin;
+
+impl Plugin for TextVariablesPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(Update, (
+            update_text_variables,
+        ).run_if(resource_exists::<SpeciesRegistry>))
+        .init_resource::<TextVariables>();
+    }
+}
+
+/// Name used until the player can choose one.
+const DEFAULT_PLAYER_NAME: &str = "Wren";
+
+/// How a language picks between singular and plural forms.
+#[derive(Reflect, Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PluralRule {
+    /// One form for 1 and another for everything else, like English.
+    #[default]
+    OneOther,
+    /// 0 and 1 share the first form, like French.
+    ZeroOneOther,
+    /// Forms for one, few and many, like Russian or Ukrainian.
+    EastSlavic,
+    /// A single form for every number, like Japanese.
+    Invariant,
+}
+
+impl PluralRule {
+    /// Index of the form to use for `n`.
+    pub fn form(self, n: u64) -> usize {
+        match self {
+            PluralRule::OneOther => usize::from(n != 1),
+            PluralRule::ZeroOneOther => usize::from(n > 1),
+            PluralRule::EastSlavic => {
+                let (ones, tens) = (n % 10, n % 100);
+                if ones == 1 && tens != 11 {
+                    0
+                } else if (2..=4).contains(&ones) && !(12..=14).contains(&tens) {
+                    1
+                } else {
+                    2
+                }
+            }
+            PluralRule::Invariant => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DaemonVariables {
+    pub nickname: String,
+    pub species: String,
+    pub level: u8,
+}
+
+/// Values placeholders are filled in with, kept in step with the party and
+/// inventory.
+#[derive(Resource, Debug, Clone)]
+pub struct TextVariables {
+    pub player_name: String,
+    pub daemons: Vec<DaemonVariables>,
+    pub items: BTreeMap<String, u32>,
+}
+
+impl Default for TextVariables {
+    fn default() -> Self {
+        TextVariables {
+            player_name: DEFAULT_PLAYER_NAME.to_string(),
+            daemons: Vec::new(),
+            items: BTreeMap::new(),
+        }
+    }
+}
+
+impl TextVariables {
+    fn value(&self, key: &str) -> Option<String> {
+        if key == "player_name" {
+            return Some(self.player_name.clone());
+        }
+        if let Some(item) = key.strip_prefix("item_count:") {
+            return Some(self.items.get(item).copied().unwrap_or(0).to_string());
+        }
+        let (slot, field) = key.strip_prefix("daemon:")?.split_once('.')?;
+        let daemon = self.daemons.get(slot.parse::<usize>().ok()?)?;
+        match field {
+            "nickname" => Some(daemon.nickname.clone()),
+            "species" => Some(daemon.species.clone()),
+            "level" => Some(daemon.level.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Fills in the placeholders of `text`.
+    pub fn resolve(&self, text: &str, plural_rule: PluralRule) -> String {
+        let mut resolved = String::with_capacity(text.len());
+        for piece in pieces(text) {
+            let placeholder = match piece {
+                Piece::Text(text) => {
+                    resolved.push_str(text);
+                    continue;
+                }
+                Piece::Placeholder(placeholder) => placeholder,
+            };
+            let mut parts = placeholder.split('|');
+            let key = parts.next().unwrap_or_default();
+            let forms: Vec<&str> = parts.collect();
+            match self.value(key) {
+                Some(value) if forms.is_empty() => resolved.push_str(&value),
+                Some(value) => {
+                    let form = value.parse::<u64>()
+                        .map_or(forms.len() - 1, |n| plural_rule.form(n).min(forms.len() - 1));
+                    resolved.push_str(&forms[form].replace('#', &value));
+                }
+                None => {
+                    warn!("Dialog text has unknown placeholder {{{}}}", placeholder);
+                    resolved.push('{');
+                    resolved.push_str(placeholder);
+                    resolved.push('}');
+                }
+            }
+        }
+        resolved
+    }
+}
+
+enum Piece<'a> {
+    Text(&'a str),
+    /// What's between the braces, forms included.
+    Placeholder(&'a str),
+}
+
+/// Splits `text` into plain text and placeholders, unescaping `{{` and `}}`.
+fn pieces(text: &str) -> Vec<Piece<'_>> {
+    let mut pieces = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(['{', '}']) {
+        pieces.push(Piece::Text(&rest[..start]));
+        let brace = &rest[start..start + 1];
+        rest = &rest[start + 1..];
+        if let Some(after) = rest.strip_prefix(brace) {
+            pieces.push(Piece::Text(brace));
+            rest = after;
+            continue;
+        }
+        let Some(end) = rest.find('}').filter(|_| brace == "{") else {
+            pieces.push(Piece::Text(brace));
+            continue;
+        };
+        pieces.push(Piece::Placeholder(&rest[..end]));
+        rest = &rest[end + 1..];
+    }
+    pieces.push(Piece::Text(rest));
+    pieces
+}
+
+/// The keys of the placeholders in `text`, without their forms.
+pub fn placeholder_keys(text: &str) -> impl Iterator<Item = &str> {
+    pieces(text).into_iter().filter_map(|piece| match piece {
+        Piece::Placeholder(placeholder) => placeholder.split('|').next(),
+        Piece::Text(_) => None,
+    })
+}
+
+fn update_text_variables(
+    party: Res<Party>,
+    inventory: Res<Inventory>,
+    registry: Res<SpeciesRegistry>,
+    mut variables: ResMut<TextVariables>,
+) {
+    if !party.is_changed() && !inventory.is_changed() && !registry.is_added() {
+        return;
+    }
+    variables.daemons = party.members().iter()
+        .map(|daemon| {
+            let species = registry.species.get(&daemon.species);
+            DaemonVariables {
+                nickname: species.map_or(daemon.species.clone(), |species| daemon.name(species).to_string()),
+                species: species.map_or(daemon.species.clone(), |species| species.name.clone()),
+                level: daemon.level,
+            }
+        })
+        .collect();
+    variables.items = inventory.items().map(|(item, count)| (item.to_string(), count)).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> TextVariables {
+        TextVariables {
+            player_name: "Wren".to_string(),
+            daemons: vec![DaemonVariables {
+                nickname: "Sparky".to_string(),
+                species: "Emberkit".to_string(),
+                level: 5,
+            }],
+            items: BTreeMap::from([("potion".to_string(), 1)]),
+        }
+    }
+
+    #[test]
+    fn fills_in_placeholders() {
+        let variables = variables();
+        assert_eq!(
+            variables.resolve("{player_name}'s {daemon:0.nickname} is level {daemon:0.level}.", PluralRule::OneOther),
+            "Wren's Sparky is level 5.",
+        );
+        assert_eq!(
+            variables.resolve("{item_count:potion|# potion|# potions}, {item_count:orb|# orb|# orbs}", PluralRule::OneOther),
+            "1 potion, 0 orbs",
+        );
+        assert_eq!(
+            variables.resolve("{item_count:orb|# orbe|# orbes}", PluralRule::ZeroOneOther),
+            "0 orbe",
+        );
+    }
+
+    #[test]
+    fn missing_placeholders_stay_visible() {
+        let variables = variables();
+        assert_eq!(variables.resolve("Hi {daemon:3.nickname}!", PluralRule::OneOther), "Hi {daemon:3.nickname}!");
+        assert_eq!(variables.resolve("{{literal}} {unclosed", PluralRule::OneOther), "{literal} {unclosed");
+    }
+
+    #[test]
+    fn east_slavic_plurals() {
+        let forms: Vec<usize> = [1, 2, 5, 11, 21, 22, 25].into_iter()
+            .map(|n| PluralRule::EastSlavic.form(n))
+            .collect();
+        assert_eq!(forms, [0, 1, 2, 2, 0, 1, 2]);
+    }
+}
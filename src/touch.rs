@@ -0,0 +1,230 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy::window::WindowResized;
+
+use crate::control::map_inputs_to_control_events;
+use crate::control::ControlStatus;
+use crate::control::GameControl;
+use crate::control::GameControlEvent;
+use crate::loading::FontAssets;
+use crate::replay::is_replaying;
+use crate::GameState;
+use crate::OuterCamera;
+use crate::HIGH_RES_LAYERS;
+use crate::RES_HEIGHT;
+use crate::RES_WIDTH;
+
+/// On-screen D-pad and buttons for touch screens, drawn in the letterbox
+/// around the game canvas.
+pub struct TouchControlsPlugin;
+
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(OnExit(GameState::AssetLoading), (
+            spawn_touch_controls,
+        ))
+        .add_systems(Update, (
+            layout_touch_controls.after(crate::fit_canvas),
+            map_touches_to_control_events
+                .before(map_inputs_to_control_events)
+                .run_if(not(is_replaying)),
+        ))
+        .init_resource::<TouchMaterials>();
+    }
+}
+
+/// Side length of a control group before scaling, in canvas pixels.
+const GROUP_SIZE: f32 = 48.;
+/// Largest scale a group is drawn at, so it stays thumb sized on big screens.
+const MAX_GROUP_SCALE: f32 = 1.5;
+const DPAD_ARM: f32 = 16.;
+const BUTTON_RADIUS: f32 = 10.;
+const START_SIZE: Vec2 = Vec2::new(20., 8.);
+const LABEL_COLOR: Color = Color::srgb(224. / 255., 240. / 255., 232. / 255.);
+
+/// The two clusters of controls, which are placed on either side of the canvas.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+enum TouchControlGroup {
+    DPad,
+    Buttons,
+}
+
+/// A touchable area that holds a control while a finger is on it.
+#[derive(Component, Debug)]
+struct TouchButton {
+    control: GameControl,
+    /// Half the size of the area, before the group is scaled.
+    half_size: Vec2,
+}
+
+#[derive(Resource)]
+struct TouchMaterials {
+    released: Handle<ColorMaterial>,
+    pressed: Handle<ColorMaterial>,
+}
+
+impl FromWorld for TouchMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.resource_mut::<Assets<ColorMaterial>>();
+        TouchMaterials {
+            released: materials.add(Color::srgba_u8(47, 76, 64, 160)),
+            pressed: materials.add(Color::srgba_u8(47, 76, 64, 230)),
+        }
+    }
+}
+
+fn spawn_touch_controls(
+    mut commands: Commands,
+    fonts: Res<FontAssets>,
+    touch_materials: Res<TouchMaterials>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let text_font = TextFont {
+        font: fonts.font.clone(),
+        font_size: 10.,
+        ..Default::default()
+    };
+
+    let arm = meshes.add(Rectangle::new(DPAD_ARM, DPAD_ARM));
+    let button = meshes.add(Circle::new(BUTTON_RADIUS));
+    let start = meshes.add(Rectangle::from_size(START_SIZE));
+
+    let touch_button = |mesh: &Handle<Mesh>, control: GameControl, half_size: Vec2, position: Vec2| (
+        Mesh2d(mesh.clone()),
+        MeshMaterial2d(touch_materials.released.clone()),
+        Transform::from_translation(position.extend(0.)),
+        HIGH_RES_LAYERS,
+        TouchButton { control, half_size },
+    );
+
+    commands.spawn((
+        Name::new("Touch D-Pad".to_string()),
+        Transform::default(),
+        Visibility::default(),
+        HIGH_RES_LAYERS,
+        TouchControlGroup::DPad,
+    )).with_children(|builder| {
+        for (control, direction) in [
+            (GameControl::Up, Vec2::Y),
+            (GameControl::Down, Vec2::NEG_Y),
+            (GameControl::Left, Vec2::NEG_X),
+            (GameControl::Right, Vec2::X),
+        ] {
+            builder.spawn(touch_button(&arm, control, Vec2::splat(DPAD_ARM / 2.), direction * DPAD_ARM));
+        }
+    });
+
+    commands.spawn((
+        Name::new("Touch Buttons".to_string()),
+        Transform::default(),
+        Visibility::default(),
+        HIGH_RES_LAYERS,
+        TouchControlGroup::Buttons,
+    )).with_children(|builder| {
+        for (control, label, position) in [
+            (GameControl::Interact, "A", Vec2::new(12., 6.)),
+            (GameControl::Cancel, "B", Vec2::new(-12., -6.)),
+        ] {
+            builder.spawn(
+                touch_button(&button, control, Vec2::splat(BUTTON_RADIUS), position)
+            ).with_child((
+                Text2d::new(label),
+                text_font.clone(),
+                TextColor(LABEL_COLOR),
+                Transform::from_xyz(0., 0., 1.),
+                HIGH_RES_LAYERS,
+            ));
+        }
+        builder.spawn(
+            touch_button(&start, GameControl::Start, START_SIZE / 2., Vec2::new(0., -GROUP_SIZE / 2. + START_SIZE.y / 2.))
+        );
+    });
+}
+
+/// Puts the groups in the letterbox left by [`crate::fit_canvas`]: beside the
+/// canvas on a landscape screen, below it on a portrait one.
+fn layout_touch_controls(
+    mut resize_events: EventReader<WindowResized>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    projections: Query<Ref<OrthographicProjection>, With<OuterCamera>>,
+    added: Query<(), Added<TouchControlGroup>>,
+    mut groups: Query<(&TouchControlGroup, &mut Transform)>,
+) {
+    let (Ok(window), Ok(projection)) = (windows.get_single(), projections.get_single()) else {
+        return;
+    };
+    let resized = resize_events.read().count() > 0;
+    if !resized && !projection.is_changed() && added.is_empty() {
+        return;
+    }
+
+    let half_view = window.size() * projection.scale / 2.;
+    let half_canvas = Vec2::new(RES_WIDTH as f32, RES_HEIGHT as f32) / 2.;
+    let margin = (half_view - half_canvas).max(Vec2::ZERO);
+
+    let (center, room) = if margin.x >= margin.y {
+        (Vec2::new(half_canvas.x + margin.x / 2., 0.), Vec2::new(margin.x, half_view.y * 2.))
+    } else {
+        (Vec2::new(half_view.x / 2., -half_canvas.y - margin.y / 2.), Vec2::new(half_view.x, margin.y))
+    };
+    let scale = (room.min_element() / GROUP_SIZE).min(MAX_GROUP_SCALE);
+
+    for (group, mut transform) in &mut groups {
+        let side = match group {
+            TouchControlGroup::DPad => Vec2::new(-1., 1.),
+            TouchControlGroup::Buttons => Vec2::ONE,
+        };
+        *transform = Transform::from_translation((center * side).extend(20.))
+            .with_scale(Vec3::splat(scale));
+    }
+}
+
+/// Sends [`GameControlEvent`]s for the buttons under any finger, the same way
+/// [`map_inputs_to_control_events`] does for keys.
+fn map_touches_to_control_events(
+    touches: Res<Touches>,
+    cameras: Query<(&Camera, &GlobalTransform), With<OuterCamera>>,
+    mut buttons: Query<(&TouchButton, &GlobalTransform, &mut MeshMaterial2d<ColorMaterial>)>,
+    touch_materials: Res<TouchMaterials>,
+    mut control: EventWriter<GameControlEvent>,
+    mut held: Local<HashSet<GameControl>>,
+) {
+    let Ok((camera, camera_transform)) = cameras.get_single() else {
+        return;
+    };
+    let fingers: Vec<Vec2> = touches.iter()
+        .filter_map(|touch| camera.viewport_to_world_2d(camera_transform, touch.position()).ok())
+        .collect();
+
+    let mut pressed = HashSet::new();
+    for (button, transform, mut material) in &mut buttons {
+        let (scale, _, translation) = transform.to_scale_rotation_translation();
+        let half_size = button.half_size * scale.truncate();
+        let touched = fingers.iter()
+            .any(|finger| (*finger - translation.truncate()).abs().cmplt(half_size).all());
+        if touched {
+            pressed.insert(button.control);
+        }
+        let target = if touched { &touch_materials.pressed } else { &touch_materials.released };
+        if material.0 != *target {
+            material.0 = target.clone();
+        }
+    }
+
+    for (controls, status) in [
+        (pressed.iter().copied().collect::<Vec<_>>(), ControlStatus::Pressed),
+        (pressed.difference(&held).copied().collect(), ControlStatus::JustPressed),
+        (held.difference(&pressed).copied().collect(), ControlStatus::JustReleased),
+    ] {
+        for c in controls {
+            control.send(GameControlEvent {
+                control: c,
+                status,
+            });
+        }
+    }
+    *held = pressed;
+}
@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+use crate::battle::engine::BattleKind;
+use crate::battle::BattleEndEvent;
+use crate::battle::DaemonSpec;
+use crate::battle::StartBattleEvent;
+use crate::battle::engine::BattleOutcome;
+use crate::dialog::CurrentDialog;
+use crate::graph::grid_transform::GridTransform;
+use crate::helpers::ron_asset::RonAssetLoader;
+use crate::loading::DaemonAssets;
+use crate::map::BlocksWalking;
+use crate::map::GridIndex;
+use crate::mob::player_in_sight;
+use crate::mob::BlocksSight;
+use crate::mob::GridDirection;
+use crate::mob::GridPosition;
+use crate::mob::MobMoveEvent;
+use crate::mob::MovementCooldown;
+use crate::mob::TriggerEvent;
+use crate::player::Player;
+use crate::state_stack::StateStack;
+use crate::story_flags::StoryFlags;
+use crate::text_loading::GameText;
+use crate::GameState;
+
+pub struct TrainerPlugin;
+
+impl Plugin for TrainerPlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_systems(Update, (
+            mark_challengers,
+            start_trainer_battle,
+            let_player_leave,
+            trainer_spot_player,
+            trainer_approach,
+        ).chain().run_if(in_state(GameState::Playing)))
+        .add_systems(Update, (
+            finish_trainer_battle,
+        ))
+        .init_asset::<TrainerList>()
+        .register_asset_loader(RonAssetLoader::<TrainerList>::new(&["trainers.ron"]))
+        .init_resource::<TrainerEncounter>()
+        .register_type::<TrainerData>()
+        .register_type::<Challenger>();
+    }
+}
+
+/// Marks a mob as a trainer, authored in Tiled alongside `TriggerOnSeePlayer`.
+///
+/// `id` selects the party from `daemons/trainers.trainers.ron` and names the
+/// defeated flag; `dialog` is the [`GameText`] dialog shown before the battle.
+#[derive(Component, Default, Debug, Reflect)]
+#[reflect(Component, Default)]
+pub struct TrainerData {
+    pub id: String,
+    pub dialog: String,
+}
+
+impl TrainerData {
+    pub fn defeated_flag(&self) -> String {
+        format!("trainer.{}.defeated", self.id)
+    }
+}
+
+/// A trainer that has not been beaten yet and will challenge the player on sight.
+#[derive(Component, Default, Debug, Reflect)]
+pub struct Challenger;
+
+/// A trainer the player just lost to. They don't challenge again until the
+/// player has left their sight, so the player can walk away.
+#[derive(Component, Default, Debug)]
+struct LettingPlayerLeave;
+
+#[derive(Reflect, Debug, Deserialize, Clone)]
+pub struct TrainerDefinition {
+    pub name: String,
+    pub party: Vec<DaemonSpec>,
+}
+
+/// Every trainer's name and party keyed by [`TrainerData::id`].
+#[derive(Asset, Reflect, Debug, Deserialize, Deref, DerefMut, Clone)]
+pub struct TrainerList(HashMap<String, TrainerDefinition>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrainerStage {
+    /// Walking up to the player.
+    Approaching,
+    /// Pre-battle dialog is showing; the battle starts once it closes.
+    Talking,
+    Battling,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ActiveTrainer {
+    trainer: Entity,
+    player: Entity,
+    stage: TrainerStage,
+}
+
+/// The trainer currently challenging the player, if any.
+/// The player can't move or interact while this is active.
+#[derive(Resource, Default, Debug)]
+pub struct TrainerEncounter(Option<ActiveTrainer>);
+
+impl TrainerEncounter {
+    pub fn is_active(&self) -> bool {
+        self.0.is_some()
+    }
+}
+
+fn mark_challengers(
+    mut commands: Commands,
+    query: Query<(Entity, &TrainerData), Added<TrainerData>>,
+    story_flags: Res<StoryFlags>,
+) {
+    for (entity, trainer) in &query {
+        if !story_flags.is_set(&trainer.defeated_flag()) {
+            commands.entity(entity).insert(Challenger);
+        }
+    }
+}
+
+fn let_player_leave(
+    mut commands: Commands,
+    trainer_query: Query<(Entity, &GridPosition, &GridDirection), With<LettingPlayerLeave>>,
+    player_query: Query<Entity, With<Player>>,
+    blocks_sight_query: Query<(), With<BlocksSight>>,
+    grid_index: Res<GridIndex>,
+) {
+    for (entity, position, direction) in &trainer_query {
+        if player_in_sight(**position, **direction, &grid_index, &player_query, &blocks_sight_query).is_none() {
+            commands.entity(entity).remove::<LettingPlayerLeave>();
+        }
+    }
+}
+
+fn trainer_spot_player(
+    mut events: EventReader<TriggerEvent>,
+    challenger_query: Query<(), (With<TrainerData>, With<Challenger>, Without<LettingPlayerLeave>)>,
+    player_query: Query<(), With<Player>>,
+    mut encounter: ResMut<TrainerEncounter>,
+) {
+    for event in events.read() {
+        if encounter.is_active() {
+            continue;
+        }
+        if challenger_query.contains(event.triggered) && player_query.contains(event.triggering) {
+            *encounter = TrainerEncounter(Some(ActiveTrainer {
+                trainer: event.triggered,
+                player: event.triggering,
+                stage: TrainerStage::Approaching,
+            }));
+        }
+    }
+}
+
+/// Single grid step from `from` towards `to`, along the longer axis.
+fn step_towards(from: GridTransform, to: GridTransform) -> GridTransform {
+    let delta = to - from;
+    if delta.x.abs() >= delta.y.abs() {
+        GridTransform::new(delta.x.signum(), 0)
+    } else {
+        GridTransform::new(0, delta.y.signum())
+    }
+}
+
+fn trainer_approach(
+    mut encounter: ResMut<TrainerEncounter>,
+    mut trainer_query: Query<(&TrainerData, &GridPosition, &mut GridDirection, &MovementCooldown), Without<Player>>,
+    mut player_query: Query<(&GridPosition, &mut GridDirection), With<Player>>,
+    block_query: Query<(), With<BlocksWalking>>,
+    grid_index: Res<GridIndex>,
+    mut mob_move_events: EventWriter<MobMoveEvent>,
+    mut current_dialog: ResMut<CurrentDialog>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut state_stack: ResMut<StateStack>,
+    game_text: Res<GameText>,
+) {
+    let Some(active) = encounter.0.as_mut() else {
+        return;
+    };
+    if active.stage != TrainerStage::Approaching {
+        return;
+    }
+    let (
+        Ok((trainer, trainer_pos, mut trainer_dir, cooldown)),
+        Ok((player_pos, mut player_dir)),
+    ) = (
+        trainer_query.get_mut(active.trainer),
+        player_query.get_mut(active.player),
+    ) else {
+        // The trainer or player went away, e.g. on a map change.
+        *encounter = TrainerEncounter(None);
+        return;
+    };
+    if !cooldown.finished() {
+        return;
+    }
+
+    let step = step_towards(**trainer_pos, **player_pos);
+    let next = **trainer_pos + step;
+    // Something in the way, like a sign, would stop the trainer for good, so
+    // they challenge from where they stand instead.
+    let blocked = grid_index.get(&next).iter().any(|&entity| block_query.contains(entity));
+    if next != **player_pos && !blocked {
+        mob_move_events.send(MobMoveEvent {
+            entity: active.trainer,
+            movement: step,
+        });
+        return;
+    }
+
+    **trainer_dir = step;
+    **player_dir = -step;
+    active.stage = TrainerStage::Talking;
+    match game_text.dialog(&trainer.dialog) {
+        Some(dialog) => {
+            *current_dialog = CurrentDialog(Some(dialog.clone()));
+            next_state.set(state_stack.push(GameState::Dialog));
+        }
+        None => warn!("Trainer `{}` has unknown dialog `{}`", trainer.id, trainer.dialog),
+    }
+}
+
+/// Starts the battle once the pre-battle dialog has closed.
+fn start_trainer_battle(
+    mut encounter: ResMut<TrainerEncounter>,
+    trainer_query: Query<&TrainerData>,
+    daemon_assets: Res<DaemonAssets>,
+    trainer_lists: Res<Assets<TrainerList>>,
+    mut battle_events: EventWriter<StartBattleEvent>,
+) {
+    let Some(active) = encounter.0.as_mut() else {
+        return;
+    };
+    if active.stage != TrainerStage::Talking {
+        return;
+    }
+    let definition = trainer_query.get(active.trainer).ok()
+        .and_then(|trainer| {
+            let definition = trainer_lists.get(&daemon_assets.trainers)
+                .and_then(|list| list.get(&trainer.id));
+            if definition.is_none() {
+                warn!("Unknown trainer: {}", trainer.id);
+            }
+            definition
+        });
+    let Some(definition) = definition else {
+        *encounter = TrainerEncounter(None);
+        return;
+    };
+
+    battle_events.send(StartBattleEvent {
+        kind: BattleKind::Trainer,
+        opponents: definition.party.clone(),
+        opponent_name: Some(definition.name.clone()),
+    });
+    active.stage = TrainerStage::Battling;
+}
+
+fn finish_trainer_battle(
+    mut commands: Commands,
+    mut events: EventReader<BattleEndEvent>,
+    mut encounter: ResMut<TrainerEncounter>,
+    trainer_query: Query<&TrainerData>,
+    mut story_flags: ResMut<StoryFlags>,
+) {
+    for event in events.read() {
+        let Some(active) = encounter.0 else {
+            continue;
+        };
+        if active.stage != TrainerStage::Battling {
+            continue;
+        }
+        if event.outcome == BattleOutcome::Won {
+            if let Ok(trainer) = trainer_query.get(active.trainer) {
+                story_flags.set(trainer.defeated_flag());
+                commands.entity(active.trainer).remove::<Challenger>();
+            }
+        } else if let Some(mut trainer) = commands.get_entity(active.trainer) {
+            trainer.insert(LettingPlayerLeave);
+        }
+        *encounter = TrainerEncounter(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::GameControl;
+    use crate::mob::Mob;
+    use crate::testing::TestApp;
+    use crate::testing::STEP_TICKS;
+
+    #[test]
+    fn blocked_trainers_challenge_from_where_they_stand() {
+        let mut app = TestApp::new();
+        app.app
+        .init_resource::<CurrentDialog>()
+        .init_resource::<GameText>()
+        .add_systems(Update, (
+            trainer_spot_player,
+            trainer_approach,
+        ).chain());
+        let player = app.spawn_player(GridTransform::ZERO);
+        app.spawn_at(GridTransform::new(0, 1), BlocksWalking);
+        let trainer = app.spawn_at(GridTransform::new(0, 2), (
+            Mob,
+            TrainerData { id: "hiker".to_string(), dialog: "hiker_challenge".to_string() },
+            Challenger,
+        ));
+        app.tick(1);
+
+        app.app.world_mut().send_event(TriggerEvent { triggering: player, triggered: trainer });
+        app.tick(STEP_TICKS * 2);
+
+        assert_eq!(app.position(trainer), GridTransform::new(0, 2));
+        let active = app.app.world().resource::<TrainerEncounter>().0.unwrap();
+        assert_eq!(active.stage, TrainerStage::Talking);
+        assert_eq!(app.app.world().get::<GridDirection>(trainer).unwrap().0, GridTransform::SOUTH);
+        assert_eq!(app.app.world().get::<GridDirection>(player).unwrap().0, GridTransform::NORTH);
+    }
+
+    #[test]
+    fn trainers_let_the_player_leave_after_losing() {
+        let mut app = TestApp::new();
+        app.app
+        .init_resource::<StoryFlags>()
+        .add_event::<BattleEndEvent>()
+        .add_systems(Update, (
+            finish_trainer_battle,
+            let_player_leave,
+            trainer_spot_player,
+        ).chain());
+        let player = app.spawn_player(GridTransform::ZERO);
+        let trainer = app.spawn_at(GridTransform::new(0, 1), (
+            Mob,
+            TrainerData { id: "hiker".to_string(), dialog: "hiker_challenge".to_string() },
+            Challenger,
+        ));
+        app.app.insert_resource(TrainerEncounter(Some(ActiveTrainer {
+            trainer,
+            player,
+            stage: TrainerStage::Battling,
+        })));
+        app.app.world_mut().send_event(BattleEndEvent { outcome: BattleOutcome::Lost });
+        app.tick(2);
+        assert!(!app.app.world().resource::<TrainerEncounter>().is_active());
+
+        app.app.world_mut().send_event(TriggerEvent { triggering: player, triggered: trainer });
+        app.tick(1);
+        assert!(!app.app.world().resource::<TrainerEncounter>().is_active());
+
+        app.hold(GameControl::Right, STEP_TICKS);
+        app.tick(1);
+        app.app.world_mut().send_event(TriggerEvent { triggering: player, triggered: trainer });
+        app.tick(1);
+        assert!(app.app.world().resource::<TrainerEncounter>().is_active());
+        assert!(app.app.world().get::<Challenger>(trainer).is_some());
+    }
+}